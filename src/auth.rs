@@ -0,0 +1,208 @@
+use crate::{Authentication, AuthenticationRole, Device, Error, Result, Secret};
+use log::*;
+use std::{ffi::OsStr, io, path::PathBuf};
+
+impl Device {
+    /// Returns the authentication object `name`.
+    pub fn authentication(&self, name: &OsStr) -> Result<Authentication<'_>> {
+        let path = self.authentication_path(name);
+        if path.file_name() != Some(name) || !path.is_dir() {
+            if !self.path.exists() {
+                return Err(Error::DeviceMissing {
+                    path: self.path.clone(),
+                    source: io::ErrorKind::NotFound.into(),
+                });
+            }
+            return Err(Error::AuthenticationNotFound {
+                name: name.to_os_string(),
+            });
+        }
+
+        self.read_authentication(path)
+    }
+
+    /// Returns the authentication object that protects the settings, i.e. the
+    /// one with the [`AuthenticationRole::BiosAdmin`] role.
+    pub fn admin_authentication(&self) -> Result<Authentication<'_>> {
+        self.authentications()?
+            .find(|a| a.role == AuthenticationRole::BiosAdmin)
+            .ok_or_else(|| Error::NoAdminAuthentication {
+                device: self.name.clone(),
+            })
+    }
+}
+
+impl<'a> Authentication<'a> {
+    /// Path to the sysfs directory of this authentication object.
+    pub fn path(&self) -> PathBuf {
+        self.device.authentication_path(&self.name)
+    }
+
+    /// Writes `secret` as the current password, which allows changing
+    /// protected settings.
+    ///
+    /// The password is cleared when the returned guard is dropped, including
+    /// when unwinding from a panic.
+    pub fn unlock(&self, secret: &Secret) -> Result<Unlocked<'_>> {
+        if !self.is_enabled {
+            warn!(
+                "authentication '{}' has no password set",
+                self.name.to_string_lossy()
+            );
+        }
+        self.write_password(secret.expose())?;
+
+        Ok(Unlocked { auth: self })
+    }
+
+    /// Sets how the firmware reads the passwords written next, on Lenovo
+    /// devices.
+    ///
+    /// Returns [`Error::UnsupportedPasswordOption`] for options that the
+    /// authentication object does not have.
+    pub fn configure(&self, options: &PasswordOptions) -> Result<()> {
+        let index = options.index.map(|i| i.to_string());
+        let settings = [
+            ("level", options.level.as_ref(), self.level.is_some()),
+            (
+                "encoding",
+                options.encoding.as_ref(),
+                self.encoding.is_some(),
+            ),
+            ("kbdlang", options.kbdlang.as_ref(), self.kbdlang.is_some()),
+            ("index", index.as_ref(), self.index.is_some()),
+        ];
+        for &(option, value, supported) in &settings {
+            let value = match value {
+                Some(v) => v,
+                None => continue,
+            };
+            if !supported {
+                return Err(Error::UnsupportedPasswordOption {
+                    name: self.name.clone(),
+                    option,
+                });
+            }
+
+            let path = self.path().join(option);
+            debug!("writing {:?} to {:?}", value, path);
+            self.device.write_value(path, OsStr::new(option), value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the current password.
+    pub fn lock(&self) -> Result<()> {
+        self.write_password("")
+    }
+
+    /// Sets a new password.
+    ///
+    /// `current` is required if a password is already set. The length of the
+    /// new password is checked before anything is written.
+    pub fn set_password(&self, current: Option<&Secret>, new: &Secret) -> Result<()> {
+        let length = new.expose().chars().count() as u64;
+        if length < self.min_password_length || length > self.max_password_length {
+            return Err(Error::PasswordLength {
+                name: self.name.clone(),
+                min: self.min_password_length,
+                max: self.max_password_length,
+            });
+        }
+
+        self.write_new_password(current, new.expose())
+    }
+
+    /// Removes the password.
+    pub fn clear_password(&self, current: &Secret) -> Result<()> {
+        self.write_new_password(Some(current), "")
+    }
+
+    fn write_new_password(&self, current: Option<&Secret>, new: &str) -> Result<()> {
+        let _unlocked = match current {
+            Some(current) => Some(self.unlock(current)?),
+            None if self.is_enabled => {
+                return Err(Error::PasswordRequired {
+                    name: self.name.clone(),
+                })
+            }
+            None => None,
+        };
+
+        let path = self.path().join("new_password");
+        debug!("writing new password to {:?}", path);
+
+        self.device
+            .backend
+            .write(&path, new.as_bytes())
+            .map_err(|e| {
+                if e.kind() == io::ErrorKind::InvalidInput {
+                    Error::PasswordRejected {
+                        name: self.name.clone(),
+                        source: e,
+                    }
+                } else {
+                    Error::write(path, e)
+                }
+            })
+    }
+
+    fn write_password(&self, password: &str) -> Result<()> {
+        self.write_file("current_password", password)
+    }
+
+    /// Writes a file of the authentication object, without logging its content.
+    pub(crate) fn write_file(&self, name: &str, value: &str) -> Result<()> {
+        let path = self.path().join(name);
+        debug!("writing {:?}", path);
+
+        self.device
+            .backend
+            .write(&path, value.as_bytes())
+            .map_err(|e| Error::write(path, e))
+    }
+}
+
+/// How the firmware reads passwords, on Lenovo devices.
+///
+/// See [`Authentication::configure`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordOptions {
+    /// `user` or `master`.
+    pub level: Option<String>,
+    /// `ascii` or `scancode`.
+    pub encoding: Option<String>,
+    /// Keyboard language, e.g. `us`, `fr` or `gr`.
+    pub kbdlang: Option<String>,
+    /// Drive of NVMe and HDD passwords.
+    pub index: Option<u32>,
+}
+
+/// An unlocked authentication object, locked again when dropped.
+#[must_use = "the password is cleared as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct Unlocked<'a> {
+    auth: &'a Authentication<'a>,
+}
+
+impl Unlocked<'_> {
+    /// Clears the password now, returning any error instead of logging it.
+    pub fn lock(self) -> Result<()> {
+        let result = self.auth.lock();
+        std::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for Unlocked<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.auth.lock() {
+            error!(
+                "failed to clear the password of '{}': {}",
+                self.auth.name.to_string_lossy(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{Authentication, Device, Error, Profile, Result, Secret, Snapshot};
+use base64::Engine;
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs1v15::SigningKey,
+    pkcs8::DecodePrivateKey,
+    signature::{SignatureEncoding, Signer as _},
+    RsaPrivateKey,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{collections::BTreeMap, ffi::OsStr, fmt, fs, path::Path};
+
+/// A change that must be signed when settings are protected by a certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Setting attribute `name` to `value`.
+    SetValue { name: String, value: String },
+    /// Saving the settings, after each [`Request::SetValue`].
+    Save,
+    /// Replacing the installed certificate with `certificate`.
+    ReplaceCertificate { certificate: String },
+    /// Removing the installed certificate.
+    ClearCertificate,
+}
+
+impl Request {
+    /// The string that is signed for this request.
+    pub fn payload(&self) -> String {
+        match self {
+            Request::SetValue { name, value } => format!("{},{}", name, value),
+            Request::Save => "save".to_string(),
+            Request::ReplaceCertificate { certificate } => certificate.clone(),
+            Request::ClearCertificate => "clear".to_string(),
+        }
+    }
+}
+
+/// Signs requests for certificate-based authentication.
+pub trait Signer: fmt::Debug {
+    /// Returns the base64-encoded signature of `request`.
+    fn sign(&self, request: &Request) -> Result<String>;
+}
+
+/// An RSA private key, signing with PKCS #1 v1.5 and SHA-256.
+pub struct PrivateKey(SigningKey<Sha256>);
+
+impl PrivateKey {
+    /// Reads a PEM-encoded PKCS #8 or PKCS #1 private key.
+    pub fn from_file(path: &Path) -> Result<PrivateKey> {
+        let pem = zeroize::Zeroizing::new(
+            fs::read_to_string(path).map_err(|e| Error::read(path.to_path_buf(), e))?,
+        );
+        let key = RsaPrivateKey::from_pkcs8_pem(&pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))
+            .map_err(|e| Error::InvalidKey {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+        Ok(PrivateKey(SigningKey::new(key)))
+    }
+}
+
+impl Signer for PrivateKey {
+    fn sign(&self, request: &Request) -> Result<String> {
+        let signature = self.0.sign(request.payload().as_bytes());
+        Ok(base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()))
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PrivateKey(<redacted>)")
+    }
+}
+
+/// Signatures made in advance, away from the machine, by payload.
+///
+/// This lets a workstation holding the private key sign the changes of a
+/// profile with [`Signatures::sign_profile`], and the target machine apply
+/// them without the key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signatures {
+    /// Device the signatures were made for.
+    pub device: String,
+    pub signatures: BTreeMap<String, String>,
+}
+
+impl Signatures {
+    /// Signs the requests needed to apply `profile` to the device described
+    /// by `snapshot`.
+    ///
+    /// Values are checked and normalized the way [`Device::apply`] does,
+    /// so that the payloads match what is written on the device.
+    pub fn sign_profile(
+        snapshot: &Snapshot,
+        profile: &Profile,
+        signer: &dyn Signer,
+    ) -> Result<Signatures> {
+        let mut errors = Vec::new();
+        let mut requests = vec![Request::Save];
+        for (name, value) in &profile.settings {
+            let attribute = match snapshot.attributes.get(name) {
+                Some(a) => a,
+                None => {
+                    errors.push(Error::AttributeNotFound { name: name.into() });
+                    continue;
+                }
+            };
+            match attribute.tpe.validate(&value.to_string()) {
+                Ok(value) => requests.push(Request::SetValue {
+                    name: name.clone(),
+                    value,
+                }),
+                Err(source) => errors.push(Error::InvalidValue {
+                    name: name.into(),
+                    source,
+                }),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Error::InvalidProfile { errors });
+        }
+
+        let signatures = requests
+            .iter()
+            .map(|r| Ok((r.payload(), signer.sign(r)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Signatures {
+            device: snapshot.device.clone(),
+            signatures,
+        })
+    }
+}
+
+impl Signer for Signatures {
+    fn sign(&self, request: &Request) -> Result<String> {
+        let payload = request.payload();
+        self.signatures
+            .get(&payload)
+            .cloned()
+            .ok_or(Error::MissingSignature { payload })
+    }
+}
+
+impl Device {
+    /// Signs every value written from now on with `signer`, for the
+    /// authentication object `authentication`.
+    ///
+    /// This is needed when the authentication object uses
+    /// [`Mechanism::Certificate`](crate::Mechanism::Certificate).
+    pub fn set_signer(&mut self, authentication: &OsStr, signer: Box<dyn Signer>) {
+        self.signer = Some((authentication.to_os_string(), signer));
+    }
+
+    /// The signer set with [`Device::set_signer`], if any.
+    pub fn signer(&self) -> Option<&dyn Signer> {
+        self.signer.as_ref().map(|(_, s)| s.as_ref())
+    }
+
+    /// Writes the signatures for setting attribute `name` to `value`, if
+    /// there is a signer.
+    pub(crate) fn sign_value(&self, name: &OsStr, value: &str) -> Result<()> {
+        let (authentication, signer) = match &self.signer {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        let auth = self.authentication(authentication)?;
+
+        let request = Request::SetValue {
+            name: name.to_string_lossy().into_owned(),
+            value: value.to_string(),
+        };
+        auth.write_file("signature", &signer.sign(&request)?)?;
+        auth.write_file("save_signature", &signer.sign(&Request::Save)?)
+    }
+}
+
+impl Authentication<'_> {
+    /// Installs a certificate, which replaces the password for changing
+    /// settings. A password must be set.
+    ///
+    /// `certificate` is the base64-encoded certificate.
+    pub fn install_certificate(&self, password: &Secret, certificate: &str) -> Result<()> {
+        let _unlocked = self.unlock(password)?;
+        self.write_file("certificate", certificate)
+    }
+
+    /// Replaces the installed certificate, signing the change with the key
+    /// of the current one.
+    pub fn replace_certificate(&self, signer: &dyn Signer, certificate: &str) -> Result<()> {
+        let request = Request::ReplaceCertificate {
+            certificate: certificate.to_string(),
+        };
+        self.write_file("signature", &signer.sign(&request)?)?;
+        self.write_file("certificate", certificate)
+    }
+
+    /// Removes the installed certificate, signing the change with its key.
+    ///
+    /// This leaves no authentication for the role: anyone can change settings.
+    pub fn clear_certificate(&self, signer: &dyn Signer) -> Result<()> {
+        self.write_file("signature", &signer.sign(&Request::ClearCertificate)?)?;
+        self.write_file("certificate", "")
+    }
+}
@@ -1,15 +1,42 @@
-use log::LevelFilter;
-use std::ffi::OsString;
+use crate::{
+    CommitMode, Device, Error, Filter, PasswordOptions, Pattern, Result, Secret, Sysfs, SYSFS_ROOT,
+};
+use log::*;
+use std::{ffi::OsString, path::Path, path::PathBuf, str::FromStr};
 use structopt::StructOpt;
 
+/// Environment variable read for the BIOS admin password if no password option is given.
+pub const PASSWORD_ENV: &str = "BIOSCTL_PASSWORD";
+
 #[derive(StructOpt, Debug)]
 #[structopt(
-    about = "Manage Dell BIOS/EFI settings",
+    about = "Manage BIOS/EFI settings",
     setting = structopt::clap::AppSettings::DisableVersion
 )]
 pub struct ProgramOptions {
-    #[structopt(long, short = "D", default_value = "dell-wmi-sysman")]
-    pub device_name: OsString,
+    /// Device to manage; required if there are several devices
+    #[structopt(long, short = "D")]
+    pub device_name: Option<OsString>,
+
+    /// Directory containing firmware-attributes devices
+    #[structopt(
+        long,
+        global = true,
+        env = "BIOSCTL_SYSFS_ROOT",
+        default_value = SYSFS_ROOT,
+        parse(from_os_str)
+    )]
+    pub sysfs_root: PathBuf,
+
+    /// Output format of read-only commands
+    #[structopt(
+        long,
+        short,
+        global = true,
+        default_value = "text",
+        possible_values = &["text", "json", "yaml"]
+    )]
+    pub output: Output,
 
     #[structopt(subcommand)]
     pub cmd: Option<Command>,
@@ -31,12 +58,113 @@ pub struct ProgramOptions {
     )]
     quiet: i8,
 
-    /// BIOS admin password for authentication
+    /// Authentication object to unlock with the password [default: the BIOS admin one]
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub auth: Option<OsString>,
+
+    /// Signs changes with this private key, for certificate-based authentication
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub private_key: Option<PathBuf>,
+
+    /// BIOS admin password; visible to other users, prefer the other password options
+    #[structopt(long, global = true, parse(from_str))]
+    pub password: Option<Secret>,
+
+    /// Reads the BIOS admin password from the first line of a file
+    #[structopt(long, global = true, parse(from_os_str), conflicts_with = "password")]
+    pub password_file: Option<PathBuf>,
+
+    /// Reads the BIOS admin password from a file descriptor
+    #[structopt(
+        long,
+        global = true,
+        conflicts_with_all = &["password", "password-file"]
+    )]
+    pub password_fd: Option<u32>,
+
+    /// Prompts for the BIOS admin password
+    #[structopt(
+        long,
+        global = true,
+        conflicts_with_all = &["password", "password-file", "password-fd"]
+    )]
+    pub ask_password: bool,
+
+    /// Encoding of the password, on Lenovo devices
+    #[structopt(long, global = true, possible_values = &["ascii", "scancode"])]
+    pub password_encoding: Option<String>,
+
+    /// Keyboard language of the password, on Lenovo devices, e.g. us, fr or gr
+    #[structopt(long, global = true)]
+    pub kbdlang: Option<String>,
+
+    /// Level of the password, on Lenovo devices
+    #[structopt(long, global = true, possible_values = &["user", "master"])]
+    pub password_level: Option<String>,
+
+    /// Drive of NVMe and HDD passwords, on Lenovo devices
     #[structopt(long, global = true)]
-    pub password: Option<String>,
+    pub password_index: Option<u32>,
 }
 
 impl ProgramOptions {
+    /// Returns the device selected with `-D`, or the only device available.
+    pub fn device(&self) -> Result<Device> {
+        if let Some(name) = &self.device_name {
+            return Ok(Device::with_root(&self.sysfs_root, name));
+        }
+
+        let mut devices = Device::enumerate_in(&self.sysfs_root, Box::new(Sysfs))?;
+        match devices.len() {
+            0 => Err(Error::NoDevice {
+                root: self.sysfs_root.clone(),
+            }),
+            1 => Ok(devices.remove(0)),
+            _ => Err(Error::SeveralDevices {
+                names: devices
+                    .iter()
+                    .map(|d| d.name.to_string_lossy().into_owned())
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Returns the BIOS admin password given with one of the password options,
+    /// or with the `BIOSCTL_PASSWORD` environment variable.
+    pub fn password(&self) -> Result<Option<Secret>> {
+        if let Some(password) = &self.password {
+            warn!("--password is visible to other users, prefer --password-file or --ask-password");
+            return Ok(Some(password.clone()));
+        }
+        if let Some(path) = &self.password_file {
+            return Secret::from_file(path).map(Some);
+        }
+        if let Some(fd) = self.password_fd {
+            return Secret::from_file(&Path::new("/proc/self/fd").join(fd.to_string())).map(Some);
+        }
+        if self.ask_password {
+            return rpassword::read_password_from_tty(Some("BIOS admin password: "))
+                .map(|p| Some(Secret::new(p)))
+                .map_err(|e| Error::read(PathBuf::from("/dev/tty"), e));
+        }
+        if let Ok(password) = std::env::var(PASSWORD_ENV) {
+            debug!("using the BIOS admin password from {}", PASSWORD_ENV);
+            return Ok(Some(Secret::new(password)));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns how the firmware must read the password, on Lenovo devices.
+    pub fn password_options(&self) -> PasswordOptions {
+        PasswordOptions {
+            level: self.password_level.clone(),
+            encoding: self.password_encoding.clone(),
+            kbdlang: self.kbdlang.clone(),
+            index: self.password_index,
+        }
+    }
+
     pub fn log_level_with_default(&self, default: i8) -> Option<LevelFilter> {
         let level = default + self.verbose - self.quiet;
         let new_level = match level {
@@ -59,10 +187,13 @@ impl ProgramOptions {
 #[derive(StructOpt, Debug)]
 pub enum Command {
     Print {
-        #[structopt(name = "SETTING")]
-        attribute: Option<OsString>,
+        #[structopt(flatten)]
+        filter: FilterOptions,
+    },
+    List {
+        #[structopt(flatten)]
+        filter: FilterOptions,
     },
-    List,
     Get {
         #[structopt(long, short)]
         default: bool,
@@ -74,12 +205,301 @@ pub enum Command {
         attribute: OsString,
     },
     Set {
+        /// Commit mode on Lenovo devices; bulk saves once at the end
+        #[structopt(long, possible_values = &["per-attribute", "bulk"])]
+        commit: Option<CommitMode>,
+
         #[structopt(name = "SETTING")]
         attribute: OsString,
 
         #[structopt(name = "VALUE")]
         value: OsString,
     },
+    /// Saves the settings staged in bulk commit mode, on Lenovo devices
+    Commit,
+    /// Sets settings back to their default value
+    ResetAttr {
+        /// Resets every setting that differs from its default value
+        #[structopt(long, conflicts_with = "SETTING", required_unless = "SETTING")]
+        all_modified: bool,
+
+        #[structopt(name = "SETTING")]
+        attributes: Vec<OsString>,
+    },
     Info,
     NeedsReboot,
+    /// Resets all settings on the next reboot, or lists the kinds of reset
+    Reset {
+        /// Confirms the reset
+        #[structopt(long)]
+        yes: bool,
+
+        /// Configuration to reset to, e.g. factory or builtinsafe
+        #[structopt(name = "KIND")]
+        kind: Option<String>,
+    },
+    Devices,
+    Export {
+        /// Output format [default: from the file extension, or toml]
+        #[structopt(long, short, possible_values = &["toml", "json"])]
+        format: Option<Format>,
+
+        /// File to write to, instead of stdout
+        #[structopt(name = "FILE", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    Apply {
+        /// Format of the profile [default: from the file extension, or toml]
+        #[structopt(long, short, possible_values = &["toml", "json"])]
+        format: Option<Format>,
+
+        /// Profile of `name = value` settings
+        #[structopt(name = "FILE", parse(from_os_str))]
+        profile: PathBuf,
+
+        /// Commit mode on Lenovo devices; bulk saves once at the end
+        #[structopt(long, possible_values = &["per-attribute", "bulk"])]
+        commit: Option<CommitMode>,
+
+        /// Signatures made in advance with `sign-request`, for certificate-based authentication;
+        /// cannot be used with --private-key
+        #[structopt(long, parse(from_os_str))]
+        signatures: Option<PathBuf>,
+    },
+    /// Signs the changes of a profile in advance, on a machine holding the private key
+    SignRequest {
+        /// Format of the files [default: from the file extension, or toml]
+        #[structopt(long, short, possible_values = &["toml", "json"])]
+        format: Option<Format>,
+
+        /// Snapshot of the target device, made with `export`
+        #[structopt(long, parse(from_os_str))]
+        snapshot: PathBuf,
+
+        /// Profile of `name = value` settings
+        #[structopt(name = "FILE", parse(from_os_str))]
+        profile: PathBuf,
+
+        /// File to write the signatures to, instead of stdout
+        #[structopt(name = "SIGNATURES", parse(from_os_str))]
+        signatures: Option<PathBuf>,
+    },
+    Diff {
+        /// Format of the files [default: from the file extension, or toml]
+        #[structopt(long, short, possible_values = &["toml", "json"])]
+        format: Option<Format>,
+
+        /// Expected profile or snapshot
+        #[structopt(name = "FILE", parse(from_os_str))]
+        expected: PathBuf,
+
+        /// Snapshot to compare with FILE, instead of the device
+        #[structopt(name = "OTHER", parse(from_os_str))]
+        other: Option<PathBuf>,
+    },
+    Check {
+        /// Format of the policy [default: from the file extension, or toml]
+        #[structopt(long, short, possible_values = &["toml", "json"])]
+        format: Option<Format>,
+
+        /// Format of the report
+        #[structopt(long, short, default_value = "text", possible_values = &["text", "json", "junit"])]
+        report: Report,
+
+        /// Policy of rules the settings must follow
+        #[structopt(name = "POLICY", parse(from_os_str))]
+        policy: PathBuf,
+    },
+    /// Manages the password of the authentication object selected with --auth
+    ///
+    /// The current password is given with the global password options.
+    Password {
+        #[structopt(subcommand)]
+        action: PasswordAction,
+    },
+    /// Manages the certificate of the authentication object selected with --auth
+    Certificate {
+        #[structopt(subcommand)]
+        action: CertificateAction,
+    },
+    /// HP Secure Platform Management
+    Spm {
+        #[structopt(subcommand)]
+        action: SpmAction,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum SpmAction {
+    /// Prints whether the signing keys are provisioned
+    Status,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum CertificateAction {
+    /// Installs a certificate; requires the current password
+    Install {
+        /// Certificate, in PEM or base64
+        #[structopt(name = "CERTIFICATE", parse(from_os_str))]
+        certificate: PathBuf,
+    },
+    /// Replaces the installed certificate; requires the current private key
+    Replace {
+        /// Certificate, in PEM or base64
+        #[structopt(name = "CERTIFICATE", parse(from_os_str))]
+        certificate: PathBuf,
+    },
+    /// Removes the installed certificate; requires the current private key
+    Clear,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum PasswordAction {
+    /// Sets a password where there is none
+    Set {
+        /// Reads the new password from the first line of a file, instead of prompting for it
+        #[structopt(long, parse(from_os_str))]
+        new_password_file: Option<PathBuf>,
+    },
+    /// Changes the current password
+    Change {
+        /// Reads the new password from the first line of a file, instead of prompting for it
+        #[structopt(long, parse(from_os_str))]
+        new_password_file: Option<PathBuf>,
+    },
+    /// Removes the current password
+    Clear,
+}
+
+impl Command {
+    /// Whether the command changes settings or authentication objects.
+    pub fn writes(&self) -> bool {
+        matches!(
+            self,
+            Command::Set { .. }
+                | Command::Commit
+                | Command::ResetAttr { .. }
+                | Command::Reset { kind: Some(_), .. }
+                | Command::Apply { .. }
+                | Command::Password { .. }
+                | Command::Certificate { .. }
+        )
+    }
+
+    /// Whether the command acts on a device.
+    pub fn needs_device(&self) -> bool {
+        !matches!(
+            self,
+            Command::Devices | Command::SignRequest { .. } | Command::Diff { other: Some(_), .. }
+        )
+    }
+}
+
+/// Options selecting the settings shown by `list` and `print`.
+#[derive(StructOpt, Debug)]
+pub struct FilterOptions {
+    /// Only settings that differ from their default value
+    #[structopt(long)]
+    pub modified: bool,
+
+    /// Only settings whose display name contains TEXT, ignoring case
+    #[structopt(long, value_name = "TEXT")]
+    pub search: Option<String>,
+
+    /// Only settings of this type
+    #[structopt(
+        long = "type",
+        value_name = "TYPE",
+        possible_values = &["enumeration", "integer", "string", "ordered-list"]
+    )]
+    pub kind: Option<String>,
+
+    /// Only settings whose current value matches PATTERN
+    #[structopt(long, value_name = "PATTERN")]
+    pub value: Option<Pattern>,
+
+    /// Names of settings, globs like `Wake*` or regular expressions like `/^Wake/`
+    #[structopt(name = "SETTING")]
+    pub names: Vec<Pattern>,
+}
+
+impl FilterOptions {
+    pub fn filter(&self) -> Filter {
+        Filter {
+            names: self.names.clone(),
+            search: self.search.clone(),
+            kind: self.kind.clone(),
+            value: self.value.clone(),
+            modified: self.modified,
+        }
+    }
+}
+
+/// Format of configuration files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            f => Err(format!("unknown format '{}'", f)),
+        }
+    }
+}
+
+/// Output format of read-only commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Text,
+    Json,
+    Yaml,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            "yaml" => Ok(Output::Yaml),
+            f => Err(format!("unknown output format '{}'", f)),
+        }
+    }
+}
+
+/// Format of the report of `check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    Text,
+    Json,
+    /// JUnit XML, for CI systems.
+    Junit,
+}
+
+impl FromStr for Report {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Report::Text),
+            "json" => Ok(Report::Json),
+            "junit" => Ok(Report::Junit),
+            f => Err(format!("unknown report format '{}'", f)),
+        }
+    }
 }
@@ -0,0 +1,73 @@
+use crate::{Device, Error, Result};
+use log::*;
+use std::{ffi::OsStr, io, str::FromStr};
+
+/// When a Lenovo device saves the values written to its attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitMode {
+    /// Every value is saved when it is written (`single` in sysfs).
+    PerAttribute,
+    /// Values are staged until [`Device::commit`] saves them all at once.
+    ///
+    /// The firmware can only save a limited number of times before a reboot,
+    /// so this is needed to change many attributes.
+    Bulk,
+}
+
+impl CommitMode {
+    fn sysfs_value(self) -> &'static str {
+        match self {
+            CommitMode::PerAttribute => "single",
+            CommitMode::Bulk => "bulk",
+        }
+    }
+}
+
+impl FromStr for CommitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "per-attribute" => Ok(CommitMode::PerAttribute),
+            "bulk" => Ok(CommitMode::Bulk),
+            m => Err(format!("unknown commit mode '{}'", m)),
+        }
+    }
+}
+
+impl Device {
+    /// The current commit mode, or `None` if the device saves every value
+    /// when it is written and has no `save_settings` file (i.e. it is not a
+    /// Lenovo device).
+    pub fn commit_mode(&self) -> Result<Option<CommitMode>> {
+        match self.read_value(self.attributes_path(), OsStr::new("save_settings")) {
+            Ok(m) if m == "bulk" => Ok(Some(CommitMode::Bulk)),
+            Ok(_) => Ok(Some(CommitMode::PerAttribute)),
+            Err(Error::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Changes the commit mode.
+    pub fn set_commit_mode(&self, mode: CommitMode) -> Result<()> {
+        self.write_save_settings(mode.sysfs_value())
+    }
+
+    /// Saves the values staged in [`CommitMode::Bulk`].
+    pub fn commit(&self) -> Result<()> {
+        self.write_save_settings("save")
+    }
+
+    fn write_save_settings(&self, value: &str) -> Result<()> {
+        if self.commit_mode()?.is_none() {
+            return Err(Error::CommitNotSupported {
+                device: self.name.clone(),
+            });
+        }
+
+        let path = self.attributes_path().join("save_settings");
+        debug!("writing {:?} to {:?}", value, path);
+
+        self.write_value(path, OsStr::new("save_settings"), value)
+    }
+}
@@ -0,0 +1,500 @@
+use crate::{Attribute, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{ffi::OsStr, fmt};
+
+/// A condition on the value of another attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+    pub attribute: String,
+    pub value: String,
+    /// Whether the condition holds when the attribute has `value`, rather than
+    /// when it has any other value.
+    pub equals: bool,
+}
+
+impl Condition {
+    /// Whether the condition holds when its attribute has `value`.
+    pub fn holds(&self, value: &str) -> bool {
+        self.value.eq_ignore_ascii_case(value) == self.equals
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is = if self.equals { "is" } else { "is not" };
+        write!(f, "'{}' {} '{}'", self.attribute, is, self.value)
+    }
+}
+
+/// When an attribute can be changed, depending on other attributes.
+///
+/// Dell devices declare them in `dell_modifier` (or `modifier`) and
+/// `dell_value_modifier`, HP devices in `prerequisites`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Constraint {
+    /// The attribute cannot be changed while the condition holds.
+    ReadOnlyIf { condition: Condition },
+    /// The attribute is hidden, and changes have no effect, while the
+    /// condition holds.
+    SuppressedIf { condition: Condition },
+    /// The attribute is forced to `value` while the condition holds.
+    ForcedIf { value: String, condition: Condition },
+    /// The attribute can only be changed while the condition holds.
+    Requires { condition: Condition },
+    /// A rule that biosctl cannot parse, and does not check.
+    Unknown { rule: String },
+}
+
+impl Constraint {
+    /// Reads the constraints of an attribute, given a function reading its
+    /// files.
+    pub(crate) fn read_all(read: impl Fn(&str) -> Option<String>) -> Vec<Constraint> {
+        let mut constraints = Vec::new();
+        if let Some(m) = read("dell_modifier").or_else(|| read("modifier")) {
+            constraints.extend(Constraint::parse_dell_modifier(&m));
+        }
+        if let Some(m) = read("dell_value_modifier") {
+            constraints.extend(Constraint::parse_dell_value_modifier(&m));
+        }
+        if let Some(p) = read("prerequisites") {
+            constraints.extend(Constraint::parse_hp_prerequisites(&p));
+        }
+
+        constraints
+    }
+
+    /// Parses a Dell attribute-level modifier, like
+    /// `[SuppressIfNot:AutoOn=SelectDays]`. Rules are each in brackets,
+    /// possibly separated by `;`.
+    fn parse_dell_modifier(modifier: &str) -> Vec<Constraint> {
+        dell_rules(modifier)
+            .map(|rule| {
+                let parsed = rule
+                    .strip_prefix('[')
+                    .and_then(|r| r.strip_suffix(']'))
+                    .and_then(|r| r.split_once(':'))
+                    .and_then(|(kind, condition)| {
+                        let (kind, equals) = match kind.strip_suffix("Not") {
+                            Some(kind) => (kind, false),
+                            None => (kind, true),
+                        };
+                        let condition = parse_dell_condition(condition, equals)?;
+                        match kind {
+                            "ReadOnlyIf" => Some(Constraint::ReadOnlyIf { condition }),
+                            "SuppressIf" => Some(Constraint::SuppressedIf { condition }),
+                            _ => None,
+                        }
+                    });
+                parsed.unwrap_or_else(|| Constraint::unknown(rule))
+            })
+            .collect()
+    }
+
+    /// Parses a Dell value-level modifier, like
+    /// `Disabled[ForceIf:SecureBoot=Enabled]`. Rules are each a value followed
+    /// by a condition in brackets, possibly separated by `;`.
+    fn parse_dell_value_modifier(modifier: &str) -> Vec<Constraint> {
+        dell_rules(modifier)
+            .map(|rule| {
+                let parsed = rule
+                    .strip_suffix(']')
+                    .and_then(|r| r.split_once('['))
+                    .and_then(|(value, r)| Some((value.trim(), r.split_once(':')?)))
+                    .filter(|(value, _)| !value.is_empty())
+                    .and_then(|(value, (kind, condition))| {
+                        let equals = match kind {
+                            "ForceIf" => true,
+                            "ForceIfNot" => false,
+                            _ => return None,
+                        };
+                        Some(Constraint::ForcedIf {
+                            value: value.to_string(),
+                            condition: parse_dell_condition(condition, equals)?,
+                        })
+                    });
+                parsed.unwrap_or_else(|| Constraint::unknown(rule))
+            })
+            .collect()
+    }
+
+    /// Parses HP prerequisites, separated by `;` outside of quotes.
+    fn parse_hp_prerequisites(prerequisites: &str) -> Vec<Constraint> {
+        let mut quoted = false;
+        prerequisites
+            .split(|c| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == ';' && !quoted
+            })
+            .filter(|p| !p.trim().is_empty())
+            .map(Constraint::parse_hp_prerequisite)
+            .collect()
+    }
+
+    /// Parses an HP prerequisite, like `CurrentValue("Legacy Boot") == "Disable"`.
+    fn parse_hp_prerequisite(prerequisite: &str) -> Constraint {
+        let parsed = prerequisite
+            .trim()
+            .strip_prefix("CurrentValue(")
+            .and_then(|r| quoted(r.trim_start()))
+            .and_then(|(attribute, r)| Some((attribute, r.trim_start().strip_prefix(')')?)))
+            .and_then(|(attribute, r)| {
+                let r = r.trim();
+                let (equals, value) = match (r.strip_prefix("=="), r.strip_prefix("!=")) {
+                    (Some(v), _) => (true, v),
+                    (_, Some(v)) => (false, v),
+                    _ => return None,
+                };
+                match quoted(value.trim())? {
+                    (value, "") => Some(Condition {
+                        attribute: attribute.to_string(),
+                        value: value.to_string(),
+                        equals,
+                    }),
+                    _ => None,
+                }
+            });
+
+        match parsed {
+            Some(condition) => Constraint::Requires { condition },
+            None => Constraint::unknown(prerequisite.trim()),
+        }
+    }
+
+    fn unknown(rule: &str) -> Constraint {
+        Constraint::Unknown {
+            rule: rule.to_string(),
+        }
+    }
+
+    /// Whether the constraint applies to the value an attribute keeps, and not
+    /// only to writing it: forced and suppressed values are undone by a later
+    /// change of the other attribute.
+    pub fn is_lasting(&self) -> bool {
+        matches!(
+            self,
+            Constraint::SuppressedIf { .. } | Constraint::ForcedIf { .. }
+        )
+    }
+
+    pub fn condition(&self) -> Option<&Condition> {
+        match self {
+            Constraint::ReadOnlyIf { condition }
+            | Constraint::SuppressedIf { condition }
+            | Constraint::ForcedIf { condition, .. }
+            | Constraint::Requires { condition } => Some(condition),
+            Constraint::Unknown { .. } => None,
+        }
+    }
+
+    /// Whether writing `value` breaks the constraint, when the attribute of
+    /// the condition has the value `other`.
+    pub fn is_violated(&self, value: &str, other: &str) -> bool {
+        match self {
+            Constraint::ReadOnlyIf { condition } | Constraint::SuppressedIf { condition } => {
+                condition.holds(other)
+            }
+            Constraint::ForcedIf {
+                value: forced,
+                condition,
+            } => condition.holds(other) && !forced.eq_ignore_ascii_case(value),
+            Constraint::Requires { condition } => !condition.holds(other),
+            Constraint::Unknown { .. } => false,
+        }
+    }
+}
+
+/// Splits Dell modifiers after each `]`, dropping the `;` between rules.
+fn dell_rules(modifier: &str) -> impl Iterator<Item = &str> {
+    modifier
+        .split_inclusive(']')
+        .map(|r| r.trim_matches(|c: char| c == ';' || c.is_whitespace()))
+        .filter(|r| !r.is_empty())
+}
+
+fn parse_dell_condition(condition: &str, equals: bool) -> Option<Condition> {
+    let (attribute, value) = condition.split_once('=')?;
+    let (attribute, value) = (attribute.trim(), value.trim());
+    if attribute.is_empty() || value.contains(['=', '[', ']']) {
+        return None;
+    }
+    Some(Condition {
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+        equals,
+    })
+}
+
+/// Splits `"quoted" rest` into `quoted` and ` rest`.
+fn quoted(s: &str) -> Option<(&str, &str)> {
+    s.strip_prefix('"')?.split_once('"')
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::ReadOnlyIf { condition } => write!(f, "read-only while {}", condition),
+            Constraint::SuppressedIf { condition } => {
+                write!(f, "has no effect while {}", condition)
+            }
+            Constraint::ForcedIf { value, condition } => {
+                write!(f, "forced to '{}' while {}", value, condition)
+            }
+            Constraint::Requires { condition } => {
+                write!(f, "can only be changed while {}", condition)
+            }
+            Constraint::Unknown { rule } => write!(f, "unknown rule '{}'", rule),
+        }
+    }
+}
+
+impl Attribute<'_> {
+    /// Returns an error if writing `value` breaks a constraint, given the
+    /// value of other attributes returned by `value_of`.
+    ///
+    /// Attributes whose value is unknown do not break constraints.
+    pub fn check_constraints(
+        &self,
+        value: &str,
+        value_of: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
+        self.check_constraints_where(value, value_of, |_| true)
+    }
+
+    /// Like [`check_constraints`](Attribute::check_constraints), for the
+    /// constraints selected by `filter`.
+    pub(crate) fn check_constraints_where(
+        &self,
+        value: &str,
+        value_of: impl Fn(&str) -> Option<String>,
+        filter: impl Fn(&Constraint) -> bool,
+    ) -> Result<()> {
+        for c in self.constraints.iter().filter(|c| filter(c)) {
+            let other = match c.condition().and_then(|c| value_of(&c.attribute)) {
+                Some(o) => o,
+                None => continue,
+            };
+            if c.is_violated(value, &other) {
+                return Err(Error::ConstraintViolated {
+                    name: self.name.clone(),
+                    constraint: c.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the constraints of the attribute against the current values of
+    /// the device.
+    pub(crate) fn check_current_constraints(&self, value: &str) -> Result<()> {
+        self.check_constraints(value, |name| {
+            self.device
+                .attribute(OsStr::new(name))
+                .ok()
+                .and_then(|a| a.current_value.ok())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(attribute: &str, value: &str, equals: bool) -> Condition {
+        Condition {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+            equals,
+        }
+    }
+
+    fn unknown(rule: &str) -> Constraint {
+        Constraint::Unknown {
+            rule: rule.to_string(),
+        }
+    }
+
+    #[test]
+    fn dell_modifier() {
+        assert_eq!(
+            Constraint::parse_dell_modifier("[ReadOnlyIf:SecureBoot=Enabled]"),
+            vec![Constraint::ReadOnlyIf {
+                condition: condition("SecureBoot", "Enabled", true)
+            }]
+        );
+        assert_eq!(
+            Constraint::parse_dell_modifier(
+                " [SuppressIfNot:AutoOn = SelectDays];[ReadOnlyIfNot:TpmSecurity=Enabled]\n"
+            ),
+            vec![
+                Constraint::SuppressedIf {
+                    condition: condition("AutoOn", "SelectDays", false)
+                },
+                Constraint::ReadOnlyIf {
+                    condition: condition("TpmSecurity", "Enabled", false)
+                },
+            ]
+        );
+        assert_eq!(
+            Constraint::parse_dell_modifier("[SuppressIf:A=B][ReadOnlyIf:C=D]"),
+            vec![
+                Constraint::SuppressedIf {
+                    condition: condition("A", "B", true)
+                },
+                Constraint::ReadOnlyIf {
+                    condition: condition("C", "D", true)
+                },
+            ]
+        );
+        assert_eq!(Constraint::parse_dell_modifier(""), vec![]);
+    }
+
+    #[test]
+    fn dell_modifier_unknown() {
+        for rule in &[
+            "[ReadOnlyIfNotNot:A=B]",
+            "[ReadOnlyNot:A=B]",
+            "[HideIf:A=B]",
+            "[ReadOnlyIf:A]",
+            "[ReadOnlyIf:=B]",
+            "[ReadOnlyIf A=B]",
+            "ReadOnlyIf:A=B",
+            "[ReadOnlyIf:A=B",
+        ] {
+            assert_eq!(
+                Constraint::parse_dell_modifier(rule),
+                vec![unknown(rule)],
+                "{}",
+                rule
+            );
+        }
+        assert_eq!(
+            Constraint::parse_dell_modifier("[ReadOnlyIf:A=B]Stray"),
+            vec![
+                Constraint::ReadOnlyIf {
+                    condition: condition("A", "B", true)
+                },
+                unknown("Stray"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dell_value_modifier() {
+        assert_eq!(
+            Constraint::parse_dell_value_modifier("Disabled[ForceIf:SecureBoot=Enabled]"),
+            vec![Constraint::ForcedIf {
+                value: "Disabled".to_string(),
+                condition: condition("SecureBoot", "Enabled", true)
+            }]
+        );
+        assert_eq!(
+            Constraint::parse_dell_value_modifier(
+                "Enabled[ForceIfNot:BootMode=Legacy]; Disabled [ForceIf:A=B]"
+            ),
+            vec![
+                Constraint::ForcedIf {
+                    value: "Enabled".to_string(),
+                    condition: condition("BootMode", "Legacy", false)
+                },
+                Constraint::ForcedIf {
+                    value: "Disabled".to_string(),
+                    condition: condition("A", "B", true)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dell_value_modifier_unknown() {
+        for rule in &[
+            "Disabled[ReadOnlyIf:A=B]",
+            "Disabled[ForceIfNotNot:A=B]",
+            "[ForceIf:A=B]",
+            "Disabled[ForceIf:A]",
+            "Disabled[ForceIf:A=B",
+            "Disabled",
+        ] {
+            assert_eq!(
+                Constraint::parse_dell_value_modifier(rule),
+                vec![unknown(rule)],
+                "{}",
+                rule
+            );
+        }
+    }
+
+    #[test]
+    fn hp_prerequisite() {
+        assert_eq!(
+            Constraint::parse_hp_prerequisite(r#"CurrentValue("Legacy Boot") == "Disable""#),
+            Constraint::Requires {
+                condition: condition("Legacy Boot", "Disable", true)
+            }
+        );
+        assert_eq!(
+            Constraint::parse_hp_prerequisite(r#" CurrentValue( "Secure Boot" )!="Enable" "#),
+            Constraint::Requires {
+                condition: condition("Secure Boot", "Enable", false)
+            }
+        );
+        for rule in &[
+            r#"CurrentValue("A") >= "1""#,
+            r#"CurrentValue("A") == Disable"#,
+            r#"CurrentValue("A") == "Disable" || CurrentValue("B") == "Disable""#,
+            r#"CurrentValue(A) == "Disable""#,
+            r#"DefaultValue("A") == "Disable""#,
+        ] {
+            assert_eq!(
+                Constraint::parse_hp_prerequisite(rule),
+                unknown(rule),
+                "{}",
+                rule
+            );
+        }
+    }
+
+    #[test]
+    fn hp_prerequisites() {
+        assert_eq!(
+            Constraint::parse_hp_prerequisites(
+                r#"CurrentValue("A") == "x;y"; CurrentValue("B") != "z";"#
+            ),
+            vec![
+                Constraint::Requires {
+                    condition: condition("A", "x;y", true)
+                },
+                Constraint::Requires {
+                    condition: condition("B", "z", false)
+                },
+            ]
+        );
+        assert_eq!(Constraint::parse_hp_prerequisites(" "), vec![]);
+    }
+
+    #[test]
+    fn violations() {
+        let c = condition("Other", "On", true);
+        let read_only = Constraint::ReadOnlyIf {
+            condition: c.clone(),
+        };
+        assert!(read_only.is_violated("x", "on"));
+        assert!(!read_only.is_violated("x", "Off"));
+
+        let forced = Constraint::ForcedIf {
+            value: "Off".to_string(),
+            condition: c.clone(),
+        };
+        assert!(forced.is_violated("On", "On"));
+        assert!(!forced.is_violated("off", "On"));
+        assert!(!forced.is_violated("On", "Off"));
+
+        let requires = Constraint::Requires { condition: c };
+        assert!(!requires.is_violated("x", "On"));
+        assert!(requires.is_violated("x", "Off"));
+        assert!(!unknown("?").is_violated("x", "On"));
+    }
+}
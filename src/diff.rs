@@ -0,0 +1,134 @@
+use crate::{AttributeType, Profile, Snapshot};
+use serde::Serialize;
+use std::fmt;
+
+/// A difference between a snapshot and an expected configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Difference {
+    /// The attribute only exists in the current configuration.
+    OnlyInCurrent { name: String },
+    /// The attribute only exists in the expected configuration.
+    OnlyInExpected { name: String },
+    /// The attribute has a different value; `current` is `None` if it cannot be read.
+    Value {
+        name: String,
+        current: Option<String>,
+        expected: String,
+    },
+    /// The type or constraints of the attribute are different.
+    Constraints {
+        name: String,
+        current: AttributeType,
+        expected: AttributeType,
+    },
+}
+
+impl Difference {
+    pub fn name(&self) -> &str {
+        match self {
+            Difference::OnlyInCurrent { name }
+            | Difference::OnlyInExpected { name }
+            | Difference::Value { name, .. }
+            | Difference::Constraints { name, .. } => name,
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::OnlyInCurrent { name } => write!(f, "{}: not expected", name),
+            Difference::OnlyInExpected { name } => write!(f, "{}: missing", name),
+            Difference::Value {
+                name,
+                current: Some(current),
+                expected,
+            } => write!(f, "{}: '{}', expected '{}'", name, current, expected),
+            Difference::Value {
+                name,
+                current: None,
+                expected,
+            } => write!(f, "{}: <Access Denied>, expected '{}'", name, expected),
+            Difference::Constraints {
+                name,
+                current,
+                expected,
+            } => write!(f, "{}: {}, expected {}", name, current, expected),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Compares this snapshot with an `expected` one.
+    ///
+    /// Values that could not be read in `expected` are not compared.
+    pub fn diff(&self, expected: &Snapshot) -> Vec<Difference> {
+        let mut diffs = Vec::new();
+
+        for (name, current) in &self.attributes {
+            let expected = match expected.attributes.get(name) {
+                Some(e) => e,
+                None => {
+                    diffs.push(Difference::OnlyInCurrent { name: name.clone() });
+                    continue;
+                }
+            };
+
+            if current.tpe != expected.tpe {
+                diffs.push(Difference::Constraints {
+                    name: name.clone(),
+                    current: current.tpe.clone(),
+                    expected: expected.tpe.clone(),
+                });
+            }
+            if let Some(e) = &expected.current_value {
+                if current.current_value.as_ref() != Some(e) {
+                    diffs.push(Difference::Value {
+                        name: name.clone(),
+                        current: current.current_value.clone(),
+                        expected: e.clone(),
+                    });
+                }
+            }
+        }
+
+        for name in expected.attributes.keys() {
+            if !self.attributes.contains_key(name) {
+                diffs.push(Difference::OnlyInExpected { name: name.clone() });
+            }
+        }
+
+        diffs
+    }
+
+    /// Compares this snapshot with the values of a profile.
+    ///
+    /// Attributes that are not in the profile are ignored. Values are
+    /// compared the way they would be written by [`Device::apply`](crate::Device::apply).
+    pub fn diff_profile(&self, profile: &Profile) -> Vec<Difference> {
+        let mut diffs = Vec::new();
+
+        for (name, value) in &profile.settings {
+            let current = match self.attributes.get(name) {
+                Some(c) => c,
+                None => {
+                    diffs.push(Difference::OnlyInExpected { name: name.clone() });
+                    continue;
+                }
+            };
+
+            let value = value.to_string();
+            let expected = current.tpe.validate(&value).unwrap_or(value);
+            if current.current_value.as_ref() != Some(&expected) {
+                diffs.push(Difference::Value {
+                    name: name.clone(),
+                    current: current.current_value.clone(),
+                    expected,
+                });
+            }
+        }
+
+        diffs
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{Constraint, ValidationError};
+use std::{ffi::OsString, fmt, io, path::PathBuf};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by biosctl.
+///
+/// New variants may be added in minor releases.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The device directory does not exist or cannot be listed.
+    DeviceMissing { path: PathBuf, source: io::Error },
+    /// No device was found while looking for one to select.
+    NoDevice { root: PathBuf },
+    /// Several devices were found while looking for one to select.
+    SeveralDevices { names: Vec<String> },
+    /// No attribute with this name exists on the device.
+    AttributeNotFound { name: OsString },
+    /// No authentication object with this name exists on the device.
+    AuthenticationNotFound { name: OsString },
+    /// The device has no authentication object with the `bios-admin` role.
+    NoAdminAuthentication { device: OsString },
+    /// The authentication object has a password, which is needed to change it.
+    PasswordRequired { name: OsString },
+    /// The new password is too short or too long.
+    PasswordLength { name: OsString, min: u64, max: u64 },
+    /// The firmware refused the new password, or the current one is wrong.
+    PasswordRejected { name: OsString, source: io::Error },
+    /// The authentication object does not have this password option.
+    UnsupportedPasswordOption {
+        name: OsString,
+        option: &'static str,
+    },
+    /// A private key cannot be parsed.
+    InvalidKey { path: PathBuf, message: String },
+    /// No signature was made in advance for this request.
+    MissingSignature { payload: String },
+    /// The device cannot reset its settings.
+    ResetNotSupported { device: OsString },
+    /// The device does not support this kind of reset.
+    InvalidResetKind { kind: String, kinds: Vec<String> },
+    /// The device saves every value when it is written, and has no commit mode.
+    CommitNotSupported { device: OsString },
+    /// The attribute has a type that biosctl does not know about.
+    UnknownAttributeType { name: OsString, tpe: String },
+    /// The default value of the attribute cannot be read.
+    NoDefaultValue { name: OsString },
+    /// The attribute is not an ordered list.
+    NotAnOrderedList { name: OsString },
+    /// The value does not fit the constraints of the attribute.
+    InvalidValue {
+        name: OsString,
+        source: ValidationError,
+    },
+    /// The attribute cannot be changed because of the value of another one.
+    ConstraintViolated {
+        name: OsString,
+        constraint: Constraint,
+    },
+    /// Attributes of a profile depend on each other through their
+    /// constraints, so they cannot be written in any order. The first name is
+    /// repeated at the end.
+    DependencyCycle { names: Vec<OsString> },
+    /// The value is not valid UTF-8.
+    NotUtf8 { name: OsString },
+    /// The firmware refused the value written to an attribute.
+    Rejected {
+        name: OsString,
+        value: String,
+        source: io::Error,
+    },
+    /// Reading or writing a file requires more privileges.
+    PermissionDenied { path: PathBuf, source: io::Error },
+    /// A file could not be read.
+    Read { path: PathBuf, source: io::Error },
+    /// A file could not be written.
+    Write { path: PathBuf, source: io::Error },
+    /// A file has content that cannot be parsed.
+    Parse { path: PathBuf, value: String },
+    /// Some settings of a profile are missing or have invalid values.
+    InvalidProfile { errors: Vec<Error> },
+}
+
+impl Error {
+    pub(crate) fn read(path: PathBuf, source: io::Error) -> Error {
+        if source.kind() == io::ErrorKind::PermissionDenied {
+            Error::PermissionDenied { path, source }
+        } else {
+            Error::Read { path, source }
+        }
+    }
+
+    pub(crate) fn write(path: PathBuf, source: io::Error) -> Error {
+        if source.kind() == io::ErrorKind::PermissionDenied {
+            Error::PermissionDenied { path, source }
+        } else {
+            Error::Write { path, source }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DeviceMissing { path, .. } => {
+                write!(f, "no device at path '{}'", path.to_string_lossy())
+            }
+            Error::NoDevice { root } => write!(
+                f,
+                "no firmware-attributes device found in '{}'",
+                root.to_string_lossy()
+            ),
+            Error::SeveralDevices { names } => write!(
+                f,
+                "several devices found ({}), select one with -D/--device-name",
+                names.join(", ")
+            ),
+            Error::AttributeNotFound { name } => {
+                write!(f, "no setting with name '{}'", name.to_string_lossy())
+            }
+            Error::AuthenticationNotFound { name } => write!(
+                f,
+                "no authentication object with name '{}'",
+                name.to_string_lossy()
+            ),
+            Error::NoAdminAuthentication { device } => write!(
+                f,
+                "device '{}' has no BIOS admin authentication object",
+                device.to_string_lossy()
+            ),
+            Error::PasswordRequired { name } => write!(
+                f,
+                "'{}' has a password, the current password is required",
+                name.to_string_lossy()
+            ),
+            Error::PasswordLength { name, min, max } => write!(
+                f,
+                "passwords of '{}' must have {} to {} characters",
+                name.to_string_lossy(),
+                min,
+                max
+            ),
+            Error::PasswordRejected { name, .. } => write!(
+                f,
+                "the firmware rejected the new password of '{}'",
+                name.to_string_lossy()
+            ),
+            Error::UnsupportedPasswordOption { name, option } => write!(
+                f,
+                "authentication '{}' does not support the '{}' password option",
+                name.to_string_lossy(),
+                option
+            ),
+            Error::InvalidKey { path, message } => write!(
+                f,
+                "invalid private key '{}': {}",
+                path.to_string_lossy(),
+                message
+            ),
+            Error::MissingSignature { payload } => {
+                write!(f, "no signature for request '{}'", payload)
+            }
+            Error::ResetNotSupported { device } => write!(
+                f,
+                "device '{}' does not support resetting its settings",
+                device.to_string_lossy()
+            ),
+            Error::InvalidResetKind { kind, kinds } => write!(
+                f,
+                "unknown reset kind '{}', expected one of: {}",
+                kind,
+                kinds.join(", ")
+            ),
+            Error::CommitNotSupported { device } => write!(
+                f,
+                "device '{}' does not support commit modes",
+                device.to_string_lossy()
+            ),
+            Error::UnknownAttributeType { name, tpe } => write!(
+                f,
+                "setting '{}' has unknown type '{}'",
+                name.to_string_lossy(),
+                tpe
+            ),
+            Error::NoDefaultValue { name } => write!(
+                f,
+                "setting '{}' has no readable default value",
+                name.to_string_lossy()
+            ),
+            Error::NotAnOrderedList { name } => write!(
+                f,
+                "setting '{}' is not an ordered list",
+                name.to_string_lossy()
+            ),
+            Error::InvalidValue { name, .. } => {
+                write!(f, "invalid value for setting '{}'", name.to_string_lossy())
+            }
+            Error::ConstraintViolated { name, constraint } => write!(
+                f,
+                "cannot change setting '{}': {}",
+                name.to_string_lossy(),
+                constraint
+            ),
+            Error::DependencyCycle { names } => {
+                write!(f, "settings depend on each other: ")?;
+                for (i, name) in names.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { " -> " };
+                    write!(f, "{}'{}'", sep, name.to_string_lossy())?;
+                }
+                Ok(())
+            }
+            Error::NotUtf8 { name } => write!(
+                f,
+                "value for setting '{}' is not valid UTF-8",
+                name.to_string_lossy()
+            ),
+            Error::Rejected { name, value, .. } => write!(
+                f,
+                "the firmware rejected value '{}' for setting '{}'",
+                value,
+                name.to_string_lossy()
+            ),
+            Error::PermissionDenied { path, .. } => {
+                write!(f, "permission denied for '{}'", path.to_string_lossy())
+            }
+            Error::Read { path, .. } => write!(f, "failed to read '{}'", path.to_string_lossy()),
+            Error::Write { path, .. } => {
+                write!(f, "failed to write '{}'", path.to_string_lossy())
+            }
+            Error::Parse { path, value } => write!(
+                f,
+                "unexpected value '{}' in '{}'",
+                value,
+                path.to_string_lossy()
+            ),
+            Error::InvalidProfile { errors } => {
+                write!(f, "invalid profile")?;
+                for (i, e) in errors.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, e)?;
+                    let mut source = std::error::Error::source(e);
+                    while let Some(s) = source {
+                        write!(f, ": {}", s)?;
+                        source = s.source();
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::DeviceMissing { source, .. }
+            | Error::Rejected { source, .. }
+            | Error::PasswordRejected { source, .. }
+            | Error::PermissionDenied { source, .. }
+            | Error::Read { source, .. }
+            | Error::Write { source, .. } => Some(source),
+            Error::InvalidValue { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::Attribute;
+use regex::Regex;
+use std::{fmt, str::FromStr};
+
+/// A pattern matched against attribute names or values.
+///
+/// Patterns between slashes, like `/^Wake/`, are regular expressions. Patterns
+/// containing `*`, `?` or `[` are globs, like `Wake*`. Anything else is matched
+/// as is. Globs and plain patterns ignore case.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Exact(String),
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Pattern::Exact(p) => p.eq_ignore_ascii_case(text),
+            Pattern::Glob(p) => p.matches_with(
+                text,
+                glob::MatchOptions {
+                    case_sensitive: false,
+                    ..Default::default()
+                },
+            ),
+            Pattern::Regex(r) => r.is_match(text),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            Regex::new(&s[1..s.len() - 1])
+                .map(Pattern::Regex)
+                .map_err(|e| format!("invalid regular expression '{}': {}", s, e))
+        } else if s.contains(&['*', '?', '['][..]) {
+            glob::Pattern::new(s)
+                .map(Pattern::Glob)
+                .map_err(|e| format!("invalid glob '{}': {}", s, e))
+        } else {
+            Ok(Pattern::Exact(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Exact(p) => write!(f, "{}", p),
+            Pattern::Glob(p) => write!(f, "{}", p),
+            Pattern::Regex(r) => write!(f, "/{}/", r),
+        }
+    }
+}
+
+/// Selects attributes by name, display name, type or value.
+///
+/// An attribute matches if it fits every condition that is set.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// The name must match one of these patterns, if there are any.
+    pub names: Vec<Pattern>,
+    /// Text contained in the display name, ignoring case.
+    pub search: Option<String>,
+    /// Type of the attribute, as in [`AttributeType::kind`](crate::AttributeType::kind).
+    pub kind: Option<String>,
+    /// The current value must match this pattern.
+    pub value: Option<Pattern>,
+    /// Whether the current value must differ from the default value.
+    pub modified: bool,
+}
+
+impl Filter {
+    pub fn matches(&self, attribute: &Attribute) -> bool {
+        let name = attribute.name.to_string_lossy();
+        if !self.names.is_empty() && !self.names.iter().any(|p| p.matches(&name)) {
+            return false;
+        }
+        if let Some(search) = &self.search {
+            let display_name = attribute.display_name.to_lowercase();
+            if !display_name.contains(&search.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(kind) = &self.kind {
+            if attribute.tpe.kind() != kind {
+                return false;
+            }
+        }
+        if let Some(value) = &self.value {
+            match &attribute.current_value {
+                Ok(v) if value.matches(v) => {}
+                _ => return false,
+            }
+        }
+
+        !self.modified || attribute.is_modified() == Some(true)
+    }
+
+    /// The name of the only attribute the filter can match, if it has a single
+    /// plain name and no other condition.
+    pub fn single_name(&self) -> Option<&str> {
+        match self.names.as_slice() {
+            [Pattern::Exact(name)]
+                if self.search.is_none()
+                    && self.kind.is_none()
+                    && self.value.is_none()
+                    && !self.modified =>
+            {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> Pattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        let p = pattern("WakeOnLan");
+        assert!(matches!(p, Pattern::Exact(_)));
+        assert!(p.matches("wakeonlan"));
+        assert!(!p.matches("WakeOnLanBoot"));
+        assert!(matches!(pattern("/"), Pattern::Exact(_)));
+        assert!(matches!(pattern(""), Pattern::Exact(_)));
+    }
+
+    #[test]
+    fn glob() {
+        let p = pattern("Wake*");
+        assert!(matches!(p, Pattern::Glob(_)));
+        assert!(p.matches("wakeonlan"));
+        assert!(!p.matches("AutoWake"));
+        assert!(pattern("Boot?").matches("Boot1"));
+        assert!(pattern("[AB]oot").matches("boot"));
+        assert!("[Boot".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn regex() {
+        let p = pattern("/^Wake/");
+        assert!(matches!(p, Pattern::Regex(_)));
+        assert!(p.matches("WakeOnLan"));
+        assert!(!p.matches("wakeOnLan"));
+        assert!(!p.matches("AutoWake"));
+        assert!(pattern("//").matches("anything"));
+        assert!(pattern("/Wake*/").matches("Wak"));
+        assert!("/(/".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        for p in &["WakeOnLan", "Wake*", "/^Wake/"] {
+            assert_eq!(pattern(p).to_string(), *p);
+        }
+    }
+}
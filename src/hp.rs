@@ -0,0 +1,36 @@
+use crate::{Device, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+
+/// Directory of the HP Sure Start files, among the attributes.
+pub(crate) const SURE_START: &str = "Sure_Start";
+
+/// Status of HP Secure Platform Management, which authenticates changes with
+/// signing keys instead of a password.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct SpmStatus {
+    /// `not provisioned`, `provisioned` or `provisioning in progress`.
+    pub state: String,
+    pub version: String,
+    pub nonce: u64,
+    pub features_in_use: u64,
+    /// Modulus of the key-encryption key, in base64.
+    pub endorsement_key_mod: String,
+    /// Modulus of the signing key, in base64.
+    pub signing_key_mod: String,
+}
+
+impl Device {
+    /// Reads the status of HP Secure Platform Management.
+    pub fn spm_status(&self) -> Result<SpmStatus> {
+        let spm = self.authentication(OsStr::new("SPM"))?;
+        let path = spm.path();
+        let status = self.read_value(path.clone(), OsStr::new("status"))?;
+
+        serde_json::from_str(&status).map_err(|_| Error::Parse {
+            path: path.join("status"),
+            value: status,
+        })
+    }
+}
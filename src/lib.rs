@@ -1,266 +1,698 @@
+mod auth;
+mod certificate;
 pub mod cli;
+mod commit;
+mod constraint;
+mod diff;
+mod error;
+mod filter;
+mod hp;
+mod policy;
+mod profile;
+mod reset;
+mod secret;
+mod snapshot;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod validation;
+
+pub use auth::{PasswordOptions, Unlocked};
+pub use certificate::{PrivateKey, Request, Signatures, Signer};
+pub use commit::CommitMode;
+pub use constraint::{Condition, Constraint};
+pub use diff::Difference;
+pub use error::{Error, Result};
+pub use filter::{Filter, Pattern};
+pub use hp::SpmStatus;
+pub use policy::{Policy, Rule, RuleResult};
+pub use profile::{ApplyReport, Outcome, Profile, ProfileValue};
+pub use secret::Secret;
+pub use snapshot::{AttributeSnapshot, Snapshot};
+pub use validation::ValidationError;
 
-use anyhow::*;
 use log::*;
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
     ffi::{OsStr, OsString},
-    os::unix::ffi::OsStrExt,
-    path::PathBuf,
+    fmt::{self, Debug},
+    io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
 };
 
+/// Default location of firmware-attributes devices in sysfs.
+pub const SYSFS_ROOT: &str = "/sys/class/firmware-attributes";
+
+/// Reads and writes the files of a firmware-attributes tree.
+///
+/// Directory listing always goes through the real filesystem; only file
+/// contents go through the backend, which lets it emulate what the kernel
+/// accepts or refuses.
+pub trait Backend: Debug {
+    fn read(&self, path: &Path) -> io::Result<String>;
+    fn write(&self, path: &Path, value: &[u8]) -> io::Result<()>;
+}
+
+/// Backend that reads and writes files directly.
+#[derive(Debug)]
+pub struct Sysfs;
+
+impl Backend for Sysfs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, value: &[u8]) -> io::Result<()> {
+        std::fs::write(path, value)
+    }
+}
+
 #[derive(Debug)]
 pub struct Device {
     pub name: OsString,
     path: PathBuf,
+    backend: Rc<dyn Backend>,
+    /// Authentication object and signer of changes, for certificate-based authentication.
+    signer: Option<(OsString, Box<dyn Signer>)>,
 }
 
 impl Device {
     pub fn from(name: &OsStr) -> Device {
-        let mut path = PathBuf::from("/sys/class/firmware-attributes");
+        Device::with_root(Path::new(SYSFS_ROOT), name)
+    }
+
+    /// Creates a device rooted at `root` instead of the default sysfs location.
+    ///
+    /// `root` must have the same layout as `/sys/class/firmware-attributes`.
+    pub fn with_root(root: &Path, name: &OsStr) -> Device {
+        Device::with_backend(root, name, Box::new(Sysfs))
+    }
+
+    /// Creates a device rooted at `root` whose files are accessed through `backend`.
+    pub fn with_backend(root: &Path, name: &OsStr, backend: Box<dyn Backend>) -> Device {
+        Device::with_shared_backend(root, name, backend.into())
+    }
+
+    fn with_shared_backend(root: &Path, name: &OsStr, backend: Rc<dyn Backend>) -> Device {
+        let mut path = root.to_path_buf();
         path.push(name);
 
         Device {
             name: name.to_os_string(),
             path,
+            backend,
+            signer: None,
         }
     }
 
-    pub fn authentications(&self) -> Result<impl Iterator<Item = Authentication>> {
-        let mut auth_path = PathBuf::from(&self.path);
-        auth_path.push("authentication");
+    /// Lists the devices registered in the default sysfs location.
+    pub fn enumerate() -> Result<Vec<Device>> {
+        Device::enumerate_in(Path::new(SYSFS_ROOT), Box::new(Sysfs))
+    }
 
-        debug!("reading device authentication path {:?}", auth_path);
+    /// Lists the devices found in `root`, sorted by name, whose files are
+    /// accessed through `backend`.
+    pub fn enumerate_in(root: &Path, backend: Box<dyn Backend>) -> Result<Vec<Device>> {
+        debug!("reading devices in {:?}", root);
 
-        Ok(auth_path
+        let backend: Rc<dyn Backend> = backend.into();
+        let mut devices = Vec::new();
+        for d in root
             .read_dir()
-            .with_context(|| {
-                format!(
-                    "failed to read authentications for device at path '{}'",
-                    self.path.to_string_lossy()
-                )
-            })?
-            .filter_map(|d| {
-                make_authentication(d).unwrap_or_else(|e| {
-                    warn!("skipping authentication with error: {}", e);
-                    for cause in e.chain().skip(1) {
-                        info!("cause: {}", cause);
-                    }
-                    None
-                })
-            }))
+            .map_err(|e| Error::read(root.to_path_buf(), e))?
+        {
+            let d = d.map_err(|e| Error::read(root.to_path_buf(), e))?;
+            // sysfs class entries are symlinks to directories
+            if d.path().is_dir() {
+                devices.push(Device::with_shared_backend(
+                    root,
+                    &d.file_name(),
+                    backend.clone(),
+                ));
+            } else {
+                trace!("ignoring non-directory '{}'", d.path().to_string_lossy());
+            }
+        }
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(devices)
+    }
+
+    /// Vendor of the platform, guessed from the name of the driver.
+    pub fn vendor(&self) -> Option<&'static str> {
+        match self.name.to_str()? {
+            "dell-wmi-sysman" => Some("Dell"),
+            "thinklmi" => Some("Lenovo"),
+            "hp-bioscfg" => Some("HP"),
+            _ => None,
+        }
+    }
+
+    /// Path to the sysfs directory of this device.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path to the DMI identification files of the machine.
+    ///
+    /// This is `/sys/class/dmi/id` for devices in the default sysfs location.
+    fn dmi_path(&self) -> Option<PathBuf> {
+        let root = self.path.parent()?;
+        Some(root.parent()?.join("dmi/id"))
+    }
+
+    fn attributes_path(&self) -> PathBuf {
+        self.path.join("attributes")
+    }
+
+    fn authentications_path(&self) -> PathBuf {
+        self.path.join("authentication")
+    }
+
+    /// Path to the directory of the authentication object `name`.
+    pub fn authentication_path(&self, name: &OsStr) -> PathBuf {
+        self.authentications_path().join(name)
     }
 
-    pub fn attributes(&self) -> Result<impl Iterator<Item = Attribute>> {
-        let mut attributes_path = PathBuf::from(&self.path);
-        attributes_path.push("attributes");
+    pub fn authentications(&self) -> Result<impl Iterator<Item = Authentication<'_>>> {
+        let auth_path = self.authentications_path();
+
+        debug!("reading device authentication path {:?}", auth_path);
+
+        Ok(self.read_dir(auth_path)?.filter_map(move |d| {
+            self.make_authentication(d).unwrap_or_else(|e| {
+                warn!("skipping authentication with error: {}", e);
+                log_causes(&e);
+                None
+            })
+        }))
+    }
+
+    pub fn attributes(&self) -> Result<impl Iterator<Item = Attribute<'_>>> {
+        let attributes_path = self.attributes_path();
 
         debug!("reading device attribute path {:?}", attributes_path);
 
-        Ok(attributes_path
-            .read_dir()
-            .with_context(|| {
-                format!(
-                    "failed to read attributes for device at path '{}'",
-                    self.path.to_string_lossy()
-                )
-            })?
-            .filter_map(move |d| {
-                self.make_attribute(d).unwrap_or_else(|e| {
-                    warn!("skipping attribute with error: {}", e);
-                    for cause in e.chain().skip(1) {
-                        info!("cause: {}", cause);
-                    }
-                    None
-                })
-            }))
+        Ok(self.read_dir(attributes_path)?.filter_map(move |d| {
+            self.make_attribute(d).unwrap_or_else(|e| {
+                warn!("skipping attribute with error: {}", e);
+                log_causes(&e);
+                None
+            })
+        }))
+    }
+
+    pub fn attribute(&self, name: &OsStr) -> Result<Attribute<'_>> {
+        let path = self.attributes_path().join(name);
+        if path.file_name() != Some(name) || !path.is_dir() {
+            if !self.path.exists() {
+                return Err(Error::DeviceMissing {
+                    path: self.path.clone(),
+                    source: io::ErrorKind::NotFound.into(),
+                });
+            }
+            return Err(Error::AttributeNotFound {
+                name: name.to_os_string(),
+            });
+        }
+
+        self.read_attribute(path)
     }
 
-    pub fn attribute(&self, name: &OsStr) -> Result<Option<Attribute>> {
-        let mut attributes = self.attributes()?;
-        Ok(attributes.find(|a| a.name == name))
+    fn read_dir(
+        &self,
+        path: PathBuf,
+    ) -> Result<impl Iterator<Item = (PathBuf, io::Result<std::fs::DirEntry>)>> {
+        let entries = path.read_dir().map_err(|e| {
+            if self.path.exists() {
+                Error::read(path.clone(), e)
+            } else {
+                Error::DeviceMissing {
+                    path: self.path.clone(),
+                    source: e,
+                }
+            }
+        })?;
+
+        Ok(entries.map(move |d| (path.clone(), d)))
     }
 
     fn make_attribute(
         &self,
-        d: Result<std::fs::DirEntry, std::io::Error>,
-    ) -> Result<Option<Attribute>> {
+        (parent, d): (PathBuf, io::Result<std::fs::DirEntry>),
+    ) -> Result<Option<Attribute<'_>>> {
         match d {
             Ok(d) => {
-                if d.file_type()?.is_dir() {
-                    let name = d.file_name();
-                    let current_value = read_value(d.path(), OsStr::new("current_value"));
-                    let default_value = read_value(d.path(), OsStr::new("default_value"));
-
-                    let display_name = read_value(d.path(), OsStr::new("display_name"))?;
-                    let display_name_lang =
-                        read_value(d.path(), OsStr::new("display_name_language_code"))?;
-
-                    let tpe_name = read_value(d.path(), OsStr::new("type"))?;
-                    let tpe = match tpe_name.as_ref() {
-                        "enumeration" => {
-                            let p_value_string =
-                                read_value(d.path(), OsStr::new("possible_values"))?;
-                            let mut p_values = Vec::new();
-                            for v in p_value_string.split(';') {
-                                p_values.push(v.to_string());
-                            }
-                            AttributeType::Enumeration {
-                                possible_values: p_values,
-                            }
-                        }
-                        "integer" => {
-                            let min: i64 =
-                                read_value(d.path(), OsStr::new("min_value"))?.parse()?;
-                            let max: i64 =
-                                read_value(d.path(), OsStr::new("max_value"))?.parse()?;
-                            let step: u64 =
-                                read_value(d.path(), OsStr::new("scalar_increment"))?.parse()?;
-
-                            AttributeType::Integer { min, max, step }
-                        }
-                        "string" => {
-                            let min_length: u64 =
-                                read_value(d.path(), OsStr::new("min_length"))?.parse()?;
-                            let max_length: u64 =
-                                read_value(d.path(), OsStr::new("min_length"))?.parse()?;
-
-                            AttributeType::String {
-                                min_length,
-                                max_length,
-                            }
-                        }
-                        a => {
-                            bail!("Unknown attribute type: '{}'", a)
-                        }
-                    };
-
-                    Ok(Some(Attribute {
-                        device: self,
-                        name,
-                        tpe,
-                        current_value,
-                        default_value,
-                        display_name,
-                        display_name_lang,
-                    }))
+                if d.file_name() == hp::SURE_START {
+                    trace!("ignoring HP Sure Start directory");
+                    Ok(None)
+                } else if d
+                    .file_type()
+                    .map_err(|e| Error::read(d.path(), e))?
+                    .is_dir()
+                {
+                    self.read_attribute(d.path()).map(Some)
                 } else {
                     trace!("ignoring non-directory '{}'", d.path().to_string_lossy());
                     Ok(None)
                 }
             }
-            Err(e) => Err(e).context("error iterating attributes"),
+            Err(e) => Err(Error::read(parent, e)),
         }
     }
 
+    fn read_attribute(&self, path: PathBuf) -> Result<Attribute<'_>> {
+        let name = path.file_name().unwrap_or_default().to_os_string();
+        let current_value = self.read_value(path.clone(), OsStr::new("current_value"));
+        let default_value = self.read_value(path.clone(), OsStr::new("default_value"));
+
+        let display_name = self.read_value(path.clone(), OsStr::new("display_name"))?;
+        let display_name_lang = self
+            .read_value(path.clone(), OsStr::new("display_name_language_code"))
+            .ok();
+
+        let constraints =
+            Constraint::read_all(|name| self.read_value(path.clone(), OsStr::new(name)).ok());
+
+        let tpe_name = self.read_value(path.clone(), OsStr::new("type"))?;
+        let tpe = match tpe_name.as_ref() {
+            "enumeration" => {
+                let p_value_string =
+                    self.read_value(path.clone(), OsStr::new("possible_values"))?;
+                let mut p_values = Vec::new();
+                for v in p_value_string.split(';') {
+                    p_values.push(v.to_string());
+                }
+                AttributeType::Enumeration {
+                    possible_values: p_values,
+                }
+            }
+            "integer" => {
+                let min = self.read_parsed(path.clone(), OsStr::new("min_value"))?;
+                let max = self.read_parsed(path.clone(), OsStr::new("max_value"))?;
+                let step = self.read_parsed(path.clone(), OsStr::new("scalar_increment"))?;
+
+                AttributeType::Integer { min, max, step }
+            }
+            "string" => {
+                let min_length = self.read_parsed(path.clone(), OsStr::new("min_length"))?;
+                let max_length = self.read_parsed(path.clone(), OsStr::new("max_length"))?;
+
+                AttributeType::String {
+                    min_length,
+                    max_length,
+                }
+            }
+            "ordered-list" => {
+                let elements = self
+                    .read_value(path.clone(), OsStr::new("elements"))?
+                    .split(';')
+                    .filter(|e| !e.is_empty())
+                    .map(str::to_string)
+                    .collect();
+
+                AttributeType::OrderedList { elements }
+            }
+            a => {
+                return Err(Error::UnknownAttributeType {
+                    name,
+                    tpe: a.to_string(),
+                })
+            }
+        };
+
+        Ok(Attribute {
+            device: self,
+            name,
+            tpe,
+            current_value,
+            default_value,
+            display_name,
+            display_name_lang,
+            constraints,
+        })
+    }
+
     pub fn modified(&self) -> Result<bool> {
-        let mut attributes_path = PathBuf::from(&self.path);
-        attributes_path.push("attributes");
+        let attributes_path = self.attributes_path();
 
         debug!("reading device attribute path {:?}", attributes_path);
 
-        let v: u8 = read_value(attributes_path, OsStr::new("pending_reboot"))?.parse()?;
+        let v: u8 = self.read_parsed(attributes_path, OsStr::new("pending_reboot"))?;
 
         match v {
             1 => Ok(true),
             _ => Ok(false),
         }
     }
-}
 
-fn make_authentication(
-    d: Result<std::fs::DirEntry, std::io::Error>,
-) -> Result<Option<Authentication>> {
-    match d {
-        Ok(d) => {
-            if d.file_type()?.is_dir() {
-                let name = d.file_name();
-                let is_enabled = !matches!(
-                    read_value(d.path(), OsStr::new("is_enabled"))?.as_ref(),
-                    "0"
-                );
-                let min_password_length =
-                    read_value(d.path(), OsStr::new("min_password_length"))?.parse()?;
-                let max_password_length =
-                    read_value(d.path(), OsStr::new("max_password_length"))?.parse()?;
-                let role = match read_value(d.path(), OsStr::new("role"))?.as_ref() {
-                    "bios-admin" => AuthenticationRole::BiosAdmin,
-                    "power-on" => AuthenticationRole::PowerOn,
-                    a => AuthenticationRole::Unknown(a.to_string()),
-                };
-
-                Ok(Some(Authentication {
-                    name,
-                    is_enabled,
-                    min_password_length,
-                    max_password_length,
-                    role,
-                }))
-            } else {
-                trace!("ignoring non-directory '{}'", d.path().to_string_lossy());
-                Ok(None)
+    fn make_authentication(
+        &self,
+        (parent, d): (PathBuf, io::Result<std::fs::DirEntry>),
+    ) -> Result<Option<Authentication<'_>>> {
+        match d {
+            Ok(d) => {
+                if d.file_type()
+                    .map_err(|e| Error::read(d.path(), e))?
+                    .is_dir()
+                {
+                    self.read_authentication(d.path()).map(Some)
+                } else {
+                    trace!("ignoring non-directory '{}'", d.path().to_string_lossy());
+                    Ok(None)
+                }
             }
+            Err(e) => Err(Error::read(parent, e)),
         }
-        Err(e) => Err(e).context("error iterating authentications"),
+    }
+
+    fn read_authentication(&self, path: PathBuf) -> Result<Authentication<'_>> {
+        let name = path.file_name().unwrap_or_default().to_os_string();
+        let is_enabled = !matches!(
+            self.read_value(path.clone(), OsStr::new("is_enabled"))?
+                .as_ref(),
+            "0"
+        );
+        // The SPM object of HP devices uses keys and has no password lengths.
+        let read_length = |name| match self.read_parsed(path.clone(), OsStr::new(name)) {
+            Err(Error::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => Ok(0),
+            r => r,
+        };
+        let min_password_length = read_length("min_password_length")?;
+        let max_password_length = read_length("max_password_length")?;
+        let role = match self.read_value(path.clone(), OsStr::new("role"))?.as_ref() {
+            "bios-admin" => AuthenticationRole::BiosAdmin,
+            "power-on" => AuthenticationRole::PowerOn,
+            a => AuthenticationRole::Unknown(a.to_string()),
+        };
+        let level = self.read_value(path.clone(), OsStr::new("level")).ok();
+        let encoding = self.read_value(path.clone(), OsStr::new("encoding")).ok();
+        let kbdlang = self.read_value(path.clone(), OsStr::new("kbdlang")).ok();
+        let index = self.read_parsed(path.clone(), OsStr::new("index")).ok();
+        let mechanism = match self.read_value(path, OsStr::new("mechanism")) {
+            Ok(m) if m == "password" => Mechanism::Password,
+            Ok(m) if m == "certificate" => Mechanism::Certificate,
+            Ok(m) => Mechanism::Unknown(m),
+            Err(e) => {
+                debug!("assuming password authentication: {}", e);
+                Mechanism::Password
+            }
+        };
+
+        Ok(Authentication {
+            device: self,
+            name,
+            is_enabled,
+            min_password_length,
+            max_password_length,
+            role,
+            mechanism,
+            level,
+            encoding,
+            kbdlang,
+            index,
+        })
+    }
+
+    fn read_value(&self, path: PathBuf, name: &OsStr) -> Result<String> {
+        let mut p = path;
+        p.push(name);
+        let mut v = self.backend.read(&p).map_err(|e| Error::read(p, e))?;
+        v = v.trim_end().to_string();
+
+        Ok(v)
+    }
+
+    /// Writes `value` to the file at `path`, reporting values refused by the
+    /// firmware as [`Error::Rejected`] for the setting `name`.
+    pub(crate) fn write_value(&self, path: PathBuf, name: &OsStr, value: &str) -> Result<()> {
+        self.backend.write(&path, value.as_bytes()).map_err(|e| {
+            if e.kind() == io::ErrorKind::InvalidInput {
+                Error::Rejected {
+                    name: name.to_os_string(),
+                    value: value.to_string(),
+                    source: e,
+                }
+            } else {
+                Error::write(path, e)
+            }
+        })
+    }
+
+    fn read_parsed<T: FromStr>(&self, path: PathBuf, name: &OsStr) -> Result<T> {
+        let v = self.read_value(path.clone(), name)?;
+        v.parse().map_err(|_| Error::Parse {
+            path: path.join(name),
+            value: v,
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Attribute<'a> {
+    #[serde(skip)]
     device: &'a Device,
+    #[serde(serialize_with = "serialize_os_str")]
     pub name: OsString,
+    #[serde(flatten)]
     pub tpe: AttributeType,
+    #[serde(serialize_with = "serialize_value")]
     pub current_value: Result<String>,
+    #[serde(serialize_with = "serialize_value")]
     pub default_value: Result<String>,
     pub display_name: String,
-    pub display_name_lang: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name_lang: Option<String>,
+    /// When the attribute can be changed, depending on other attributes.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<Constraint>,
 }
 
 impl<'a> Attribute<'a> {
+    /// Writes a new value to the attribute.
+    ///
+    /// The value is checked against the type of the attribute before writing;
+    /// a [`ValidationError`] is returned if it does not fit.
     pub fn set_value(&mut self, value: &OsStr) -> Result<()> {
-        let mut p = PathBuf::from(&self.device.path);
-        p.push("attributes");
+        let value = value.to_str().ok_or_else(|| Error::NotUtf8 {
+            name: self.name.clone(),
+        })?;
+        let value = self
+            .tpe
+            .validate(value)
+            .map_err(|source| Error::InvalidValue {
+                name: self.name.clone(),
+                source,
+            })?;
+
+        let mut p = self.device.attributes_path();
         p.push(&self.name);
-        p.push("current_value");
+
+        self.check_current_constraints(&value)?;
 
         debug!("writing value {:?} to attribute {:?}", value, p);
+        self.device.sign_value(&self.name, &value)?;
 
-        std::fs::write(&p, value.as_bytes())?;
+        self.device
+            .write_value(p.join("current_value"), &self.name, &value)?;
 
-        self.current_value = read_value(self.device.path.to_path_buf(), &self.name);
+        self.current_value = self.device.read_value(p, OsStr::new("current_value"));
 
         Ok(())
     }
+
+    /// Whether the current value differs from the default value, or `None` if
+    /// either of them cannot be read.
+    pub fn is_modified(&self) -> Option<bool> {
+        match (&self.current_value, &self.default_value) {
+            (Ok(current), Ok(default)) => Some(current != default),
+            _ => None,
+        }
+    }
+
+    /// Sets the attribute back to its default value.
+    ///
+    /// Returns whether the value was written: nothing is written if the
+    /// attribute already has its default value.
+    pub fn restore_default(&mut self) -> Result<bool> {
+        let default = match &self.default_value {
+            Ok(d) => d.clone(),
+            Err(_) => {
+                return Err(Error::NoDefaultValue {
+                    name: self.name.clone(),
+                })
+            }
+        };
+        if matches!(&self.current_value, Ok(current) if *current == default) {
+            return Ok(false);
+        }
+
+        self.set_value(OsStr::new(&default))?;
+        Ok(true)
+    }
+
+    /// Reorders an ordered-list attribute.
+    ///
+    /// `order` must contain every element of the list exactly once.
+    pub fn set_order<S: AsRef<str>>(&mut self, order: &[S]) -> Result<()> {
+        if !matches!(self.tpe, AttributeType::OrderedList { .. }) {
+            return Err(Error::NotAnOrderedList {
+                name: self.name.clone(),
+            });
+        }
+
+        let order: Vec<_> = order.iter().map(AsRef::as_ref).collect();
+        self.set_value(OsStr::new(&order.join(";")))
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
 pub enum AttributeType {
     Integer { min: i64, max: i64, step: u64 },
     String { min_length: u64, max_length: u64 },
     Enumeration { possible_values: Vec<String> },
+    OrderedList { elements: Vec<String> },
 }
 
-#[derive(Debug)]
-pub struct Authentication {
+impl AttributeType {
+    /// Name of the type in sysfs: `integer`, `string`, `enumeration` or
+    /// `ordered-list`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AttributeType::Integer { .. } => "integer",
+            AttributeType::String { .. } => "string",
+            AttributeType::Enumeration { .. } => "enumeration",
+            AttributeType::OrderedList { .. } => "ordered-list",
+        }
+    }
+}
+
+impl fmt::Display for AttributeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeType::Integer { min, max, step } => {
+                write!(f, "integer from {} to {} by {}", min, max, step)
+            }
+            AttributeType::String {
+                min_length,
+                max_length,
+            } => write!(f, "string of {} to {} characters", min_length, max_length),
+            AttributeType::Enumeration { possible_values } => {
+                write!(f, "one of [{}]", possible_values.join(", "))
+            }
+            AttributeType::OrderedList { elements } => {
+                write!(f, "ordered list of [{}]", elements.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Authentication<'a> {
+    #[serde(skip)]
+    device: &'a Device,
+    #[serde(serialize_with = "serialize_os_str")]
     pub name: OsString,
     pub is_enabled: bool,
     pub min_password_length: u64,
     pub max_password_length: u64,
     pub role: AuthenticationRole,
+    pub mechanism: Mechanism,
+    /// Password level on Lenovo devices, `user` or `master`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// How passwords are encoded on Lenovo devices, `ascii` or `scancode`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Keyboard language of passwords on Lenovo devices, e.g. `us`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kbdlang: Option<String>,
+    /// Drive of NVMe and HDD passwords on Lenovo devices.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AuthenticationRole {
     BiosAdmin,
     PowerOn,
     Unknown(String),
 }
 
-fn read_value(path: PathBuf, name: &OsStr) -> Result<String> {
-    let mut p = path;
-    p.push(name);
-    let mut v = std::fs::read_to_string(p)
-        .with_context(|| format!("failed to read value '{}'", name.to_string_lossy()))?;
-    v = v.trim_end().to_string();
+impl Serialize for AuthenticationRole {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            AuthenticationRole::BiosAdmin => "bios-admin",
+            AuthenticationRole::PowerOn => "power-on",
+            AuthenticationRole::Unknown(r) => r,
+        })
+    }
+}
 
-    Ok(v)
+/// How an authentication object authenticates changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mechanism {
+    Password,
+    /// Changes are signed with the private key of an installed certificate.
+    Certificate,
+    Unknown(String),
+}
+
+impl Serialize for Mechanism {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Mechanism::Password => "password",
+            Mechanism::Certificate => "certificate",
+            Mechanism::Unknown(m) => m,
+        })
+    }
+}
+
+/// A value of an attribute, as written in structured output.
+///
+/// Values that cannot be read are represented by an error instead of a
+/// placeholder string.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ReadValue<'a> {
+    Value(&'a str),
+    Unreadable { error: &'static str },
+}
+
+impl<'a> From<&'a Result<String>> for ReadValue<'a> {
+    fn from(value: &'a Result<String>) -> Self {
+        match value {
+            Ok(v) => ReadValue::Value(v),
+            Err(Error::PermissionDenied { .. }) => ReadValue::Unreadable {
+                error: "access-denied",
+            },
+            Err(Error::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                ReadValue::Unreadable { error: "missing" }
+            }
+            Err(_) => ReadValue::Unreadable {
+                error: "read-failed",
+            },
+        }
+    }
+}
+
+fn serialize_value<S: Serializer>(
+    value: &Result<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    ReadValue::from(value).serialize(serializer)
+}
+
+fn serialize_os_str<S: Serializer>(s: &OsString, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&s.to_string_lossy())
+}
+
+fn log_causes(e: &dyn std::error::Error) {
+    let mut cause = e.source();
+    while let Some(c) = cause {
+        info!("cause: {}", c);
+        cause = c.source();
+    }
 }
@@ -1,49 +1,30 @@
 use anyhow::*;
 use biosctl::{
-    cli::{Command, ProgramOptions},
-    Attribute, AttributeType, AuthenticationRole, Device,
+    cli::{
+        CertificateAction, Command, Format, Output, PasswordAction, ProgramOptions, Report,
+        SpmAction,
+    },
+    Attribute, AttributeType, Authentication, AuthenticationRole, CommitMode, Device, Filter,
+    Mechanism, Outcome, PasswordOptions, Policy, PrivateKey, Profile, ReadValue, RuleResult,
+    Secret, Signatures, Signer, Snapshot, Sysfs,
 };
 use env_logger::{Builder, Env};
 use log::*;
+use serde::{Deserialize, Serialize};
 use std::{
-    ffi::OsStr,
+    ffi::{OsStr, OsString},
     fs,
     io::{stdout, Write},
+    path::Path,
     process::exit,
 };
 // Bring the StructOpt trait into scope so that ProgramOptions::clap() and ::from_clap() work.
 use structopt::StructOpt;
-// Import Arg from structopt's clap module (clap v2.33.3)
-use structopt::clap::Arg;
-
-const ADMIN_PASSWORD_PATH: &str = "/sys/class/firmware-attributes/dell-wmi-sysman/authentication/Admin/current_password";
 
 type ReturnCode = i32;
 
-/// Writes the given password to the sysfs file to unlock BIOS settings.
-fn write_admin_password(password: &str) -> Result<()> {
-    fs::write(ADMIN_PASSWORD_PATH, password)
-        .with_context(|| format!("writing admin password to {}", ADMIN_PASSWORD_PATH))?;
-    Ok(())
-}
-
-/// Clears the admin password from the sysfs file.
-fn clear_admin_password() -> Result<()> {
-    fs::write(ADMIN_PASSWORD_PATH, "")
-        .with_context(|| format!("clearing admin password at {}", ADMIN_PASSWORD_PATH))?;
-    Ok(())
-}
-
 fn main() -> Result<()> {
-    // Extend the existing ProgramOptions clap app with a new --password flag.
-    let mut app = ProgramOptions::clap();
-    app = app.arg(
-        Arg::with_name("password")
-            .long("password")
-            .help("BIOS admin password for authentication")
-            .takes_value(true),
-    );
-    let options_matches = app.get_matches();
+    let options_matches = ProgramOptions::clap().get_matches();
     let options = ProgramOptions::from_clap(&options_matches);
 
     if options.version {
@@ -72,77 +53,442 @@ fn main() -> Result<()> {
         exit(1);
     }
 
-    // If a BIOS password was provided, unlock the BIOS by writing it to the sysfs node.
-    let password = options_matches.value_of("password");
-    if let Some(pwd) = password {
-        if let Err(e) = write_admin_password(pwd) {
-            eprintln!("Failed to unlock BIOS: {}", e);
-            exit(1);
+    if let Some(cmd) = options.cmd.as_ref().filter(|c| !c.needs_device()) {
+        match run_without_device(&options, cmd) {
+            Ok(i) => exit(i),
+            Err(e) => exit(report_error(&e)),
         }
-        println!("BIOS unlocked for changes.");
     }
 
-    let retcode = match run(options) {
-        Ok(i) => i,
-        Err(e) => {
-            println!("Error: {}", e);
-            for cause in e.chain().skip(1) {
-                info!("cause: {}", cause);
-            }
-            // Attempt to clear the password even if an error occurred.
-            if password.is_some() {
-                if let Err(e) = clear_admin_password() {
-                    eprintln!("Failed to clear BIOS password: {}", e);
-                }
+    let mut device = match options.device() {
+        Ok(d) => d,
+        Err(e) => exit(report_error(&e.into())),
+    };
+
+    let password = match options.password() {
+        Ok(p) => p,
+        Err(e) => exit(report_error(&e.into())),
+    };
+
+    let auth = options.auth.as_deref();
+    let signer = match read_signer(&options, &device) {
+        Ok(s) => s,
+        Err(e) => exit(report_error(&e)),
+    };
+    if let Some(signer) = signer {
+        match select_authentication(&device, auth) {
+            Ok(a) => {
+                let name = a.name;
+                device.set_signer(&name, signer);
             }
-            exit(1);
+            Err(e) => exit(report_error(&e.into())),
         }
+    }
+
+    // The password options change the state of the driver, so they are only
+    // written right before a password, for a change.
+    let password_options = options.password_options();
+    let configured = match &options.cmd {
+        Some(Command::Password { .. }) => true,
+        Some(Command::Certificate {
+            action: CertificateAction::Install { .. },
+        }) => password.is_some(),
+        Some(c) => c.writes() && password.is_some(),
+        None => false,
     };
+    if password_options != PasswordOptions::default() && !configured {
+        warn!("password options are ignored without a password and a command making changes");
+    }
 
-    // Clear the BIOS password if one was used.
-    if password.is_some() {
-        if let Err(e) = clear_admin_password() {
+    let result = if let Some(Command::Password { action }) = &options.cmd {
+        manage_password(&device, auth, action, password.as_ref(), &password_options)
+    } else if let Some(Command::Certificate { action }) = &options.cmd {
+        manage_certificate(&device, auth, action, password.as_ref(), &password_options)
+    } else if let Some(password) = &password {
+        run_unlocked(options, &device, password)
+    } else if let Some(Command::Reset { kind: Some(_), .. }) = &options.cmd {
+        // Without the password the firmware ignores the reset; say so upfront.
+        match select_authentication(&device, auth) {
+            Ok(a) if a.is_enabled => Err(biosctl::Error::PasswordRequired { name: a.name }.into()),
+            _ => run(options, &device),
+        }
+    } else {
+        run(options, &device)
+    };
+    match result {
+        Ok(i) => exit(i),
+        Err(e) => exit(report_error(&e)),
+    }
+}
+
+/// Returns the private key given with `--private-key`, or the signatures given
+/// with `apply --signatures` for `device`.
+fn read_signer(options: &ProgramOptions, device: &Device) -> Result<Option<Box<dyn Signer>>> {
+    let signatures = match &options.cmd {
+        Some(Command::Apply {
+            signatures: Some(path),
+            ..
+        }) => Some(path),
+        _ => None,
+    };
+
+    // --private-key is global, so clap cannot make it conflict with --signatures.
+    match (&options.private_key, signatures) {
+        (Some(_), Some(_)) => bail!("--signatures cannot be used with --private-key"),
+        (Some(key), None) => Ok(Some(Box::new(PrivateKey::from_file(key)?))),
+        (None, Some(path)) => {
+            let signatures: Signatures = read_file(path, None)
+                .with_context(|| format!("invalid signatures '{}'", path.to_string_lossy()))?;
+            if signatures.device != device.name.to_string_lossy() {
+                bail!(
+                    "signatures '{}' were made for device '{}', not '{}'",
+                    path.to_string_lossy(),
+                    signatures.device,
+                    device.name.to_string_lossy()
+                );
+            }
+            Ok(Some(Box::new(signatures)))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Returns the authentication object selected with `--auth`, or the BIOS admin one.
+fn select_authentication<'a>(
+    device: &'a Device,
+    name: Option<&OsStr>,
+) -> biosctl::Result<Authentication<'a>> {
+    match name {
+        Some(name) => device.authentication(name),
+        None => device.admin_authentication(),
+    }
+}
+
+/// Unlocks the authentication object selected with `--auth` for the duration of the command.
+fn run_unlocked(options: ProgramOptions, device: &Device, password: &Secret) -> Result<ReturnCode> {
+    let auth = select_authentication(device, options.auth.as_deref())?;
+
+    // The guard below does not run if the process is killed by a signal.
+    let password_path = auth.path().join("current_password");
+    ctrlc::set_handler(move || {
+        if let Err(e) = fs::write(&password_path, "") {
             eprintln!("Failed to clear BIOS password: {}", e);
-            exit(1);
         }
-        println!("BIOS password cleared.");
+        exit(130);
+    })?;
+
+    if options.cmd.as_ref().map_or(false, Command::writes) {
+        auth.configure(&options.password_options())?;
+    }
+    let guard = auth.unlock(password)?;
+    eprintln!("BIOS unlocked for changes.");
+
+    let result = run(options, device);
+    match guard.lock() {
+        Ok(()) => eprintln!("BIOS password cleared."),
+        Err(e) if result.is_ok() => return Err(e).context("failed to clear BIOS password"),
+        Err(e) => eprintln!("Failed to clear BIOS password: {}", e),
+    }
+
+    result
+}
+
+fn manage_password(
+    device: &Device,
+    auth: Option<&OsStr>,
+    action: &PasswordAction,
+    current: Option<&Secret>,
+    options: &PasswordOptions,
+) -> Result<ReturnCode> {
+    let auth = select_authentication(device, auth)?;
+    let name = auth.name.to_string_lossy();
+    trace!("managing password of {:?} ({:?})", auth.name, action);
+
+    match action {
+        PasswordAction::Set { new_password_file } => {
+            if auth.is_enabled {
+                bail!("'{}' already has a password, use 'password change'", name);
+            }
+            let new = new_password(new_password_file.as_deref())?;
+            auth.configure(options)?;
+            auth.set_password(current, &new)?;
+        }
+        PasswordAction::Change { new_password_file } => {
+            if !auth.is_enabled {
+                bail!("'{}' has no password, use 'password set'", name);
+            }
+            let new = new_password(new_password_file.as_deref())?;
+            auth.configure(options)?;
+            auth.set_password(current, &new)?;
+        }
+        PasswordAction::Clear => {
+            if !auth.is_enabled {
+                bail!("'{}' has no password", name);
+            }
+            let current = current.ok_or_else(|| biosctl::Error::PasswordRequired {
+                name: auth.name.clone(),
+            })?;
+            auth.configure(options)?;
+            auth.clear_password(current)?;
+        }
+    }
+
+    let expected = !matches!(action, PasswordAction::Clear);
+    if device.authentication(&auth.name)?.is_enabled == expected {
+        let done = if expected { "set" } else { "cleared" };
+        println!("Password of '{}' {}.", name, done);
+    } else {
+        println!(
+            "Password of '{}' written, but the firmware does not report the change yet.",
+            name
+        );
+    }
+
+    Ok(0)
+}
+
+fn manage_certificate(
+    device: &Device,
+    auth: Option<&OsStr>,
+    action: &CertificateAction,
+    password: Option<&Secret>,
+    options: &PasswordOptions,
+) -> Result<ReturnCode> {
+    let auth = select_authentication(device, auth)?;
+    let name = auth.name.to_string_lossy();
+    trace!("managing certificate of {:?} ({:?})", auth.name, action);
+
+    let signer = || {
+        device
+            .signer()
+            .ok_or_else(|| anyhow!("the current private key is required, use --private-key"))
+    };
+    match action {
+        CertificateAction::Install { certificate } => {
+            let password = password.ok_or_else(|| biosctl::Error::PasswordRequired {
+                name: auth.name.clone(),
+            })?;
+            let certificate = read_certificate(certificate)?;
+            auth.configure(options)?;
+            auth.install_certificate(password, &certificate)?;
+            println!("Certificate of '{}' installed.", name);
+        }
+        CertificateAction::Replace { certificate } => {
+            auth.replace_certificate(signer()?, &read_certificate(certificate)?)?;
+            println!("Certificate of '{}' replaced.", name);
+        }
+        CertificateAction::Clear => {
+            auth.clear_certificate(signer()?)?;
+            println!("Certificate of '{}' removed.", name);
+        }
+    }
+    println!("\nReboot pending: the change takes effect after a reboot.");
+
+    Ok(0)
+}
+
+/// Reads a certificate as base64, without PEM armor.
+fn read_certificate(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.to_string_lossy()))?;
+
+    Ok(content
+        .lines()
+        .filter(|l| !l.starts_with("-----"))
+        .map(str::trim)
+        .collect())
+}
+
+/// Reads a new password from a file, or prompts for it twice.
+fn new_password(file: Option<&Path>) -> Result<Secret> {
+    if let Some(file) = file {
+        return Ok(Secret::from_file(file)?);
+    }
+
+    let new = Secret::new(rpassword::read_password_from_tty(Some("New password: "))?);
+    let repeated = Secret::new(rpassword::read_password_from_tty(Some(
+        "Repeat new password: ",
+    ))?);
+    if new.expose() != repeated.expose() {
+        bail!("passwords do not match");
+    }
+
+    Ok(new)
+}
+
+/// Prints an error and returns the matching exit code.
+fn report_error(e: &Error) -> ReturnCode {
+    eprintln!("Error: {:#}", e);
+
+    e.chain()
+        .find_map(|c| c.downcast_ref::<biosctl::Error>())
+        .map_or(1, error_code)
+}
+
+fn error_code(e: &biosctl::Error) -> ReturnCode {
+    match e {
+        biosctl::Error::DeviceMissing { .. }
+        | biosctl::Error::NoDevice { .. }
+        | biosctl::Error::SeveralDevices { .. } => 2,
+        biosctl::Error::AttributeNotFound { .. }
+        | biosctl::Error::AuthenticationNotFound { .. }
+        | biosctl::Error::UnsupportedPasswordOption { .. }
+        | biosctl::Error::CommitNotSupported { .. }
+        | biosctl::Error::NoAdminAuthentication { .. }
+        | biosctl::Error::ResetNotSupported { .. } => 3,
+        biosctl::Error::PermissionDenied { .. }
+        | biosctl::Error::PasswordRequired { .. }
+        | biosctl::Error::MissingSignature { .. } => 4,
+        biosctl::Error::InvalidValue { .. }
+        | biosctl::Error::NotUtf8 { .. }
+        | biosctl::Error::ConstraintViolated { .. }
+        | biosctl::Error::DependencyCycle { .. }
+        | biosctl::Error::NotAnOrderedList { .. }
+        | biosctl::Error::InvalidProfile { .. }
+        | biosctl::Error::PasswordLength { .. }
+        | biosctl::Error::InvalidResetKind { .. } => 5,
+        biosctl::Error::Rejected { .. } | biosctl::Error::PasswordRejected { .. } => 6,
+        biosctl::Error::UnknownAttributeType { .. } => 7,
+        _ => 1,
+    }
+}
+
+fn run_without_device(options: &ProgramOptions, cmd: &Command) -> Result<ReturnCode> {
+    match cmd {
+        Command::Devices => {
+            list_devices(&options.sysfs_root, options.output)?;
+            Ok(0)
+        }
+        Command::SignRequest {
+            format,
+            snapshot,
+            profile,
+            signatures,
+        } => {
+            let key = options
+                .private_key
+                .as_deref()
+                .ok_or_else(|| anyhow!("a private key is required, use --private-key"))?;
+            sign_request(
+                &PrivateKey::from_file(key)?,
+                *format,
+                snapshot,
+                profile,
+                signatures.as_deref(),
+            )?;
+            Ok(0)
+        }
+        Command::Diff {
+            format,
+            expected,
+            other: Some(other),
+        } => {
+            let current = read_file(other, *format)
+                .with_context(|| format!("invalid snapshot '{}'", other.to_string_lossy()))?;
+            diff(current, *format, expected, options.output)
+        }
+        c => unreachable!("command needs a device: {:?}", c),
     }
-    exit(retcode)
 }
 
-fn run(options: ProgramOptions) -> Result<ReturnCode> {
-    let cmd = options.cmd.ok_or_else(|| anyhow!("should never happen: no command"))?;
+fn run(options: ProgramOptions, device: &Device) -> Result<ReturnCode> {
+    let output = options.output;
+    let cmd = options
+        .cmd
+        .ok_or_else(|| anyhow!("should never happen: no command"))?;
     match cmd {
-        Command::Print { attribute } => {
-            print_device(&options.device_name, attribute.as_deref())?;
+        Command::Print { filter } => {
+            print_device(device, &filter.filter(), output)?;
         }
-        Command::List => {
-            list_device(&options.device_name)?;
+        Command::List { filter } => {
+            list_device(device, &filter.filter(), output)?;
         }
         Command::Get {
             default,
             name,
             attribute,
         } => {
-            print_attribute_value(&options.device_name, &attribute, default, name)?;
+            print_attribute_value(device, &attribute, default, name, output)?;
         }
         Command::Info => {
-            device_info(&options.device_name)?;
+            device_info(device, output)?;
         }
-        Command::Set { attribute, value } => {
-            let device = Device::from(&options.device_name);
-            if let Some(mut attr) = device.attribute(&attribute)? {
-                attr.set_value(&value)?;
-            } else {
-                bail!("no setting with name '{}'", attribute.to_string_lossy());
-            }
+        Command::ResetAttr {
+            all_modified,
+            attributes,
+        } => {
+            return reset_attributes(device, &attributes, all_modified);
+        }
+        Command::Set {
+            commit,
+            attribute,
+            value,
+        } => {
+            return with_commit_mode(device, commit, || {
+                let mut attr = device.attribute(&attribute)?;
+                if let AttributeType::OrderedList { .. } = attr.tpe {
+                    let value = value.to_string_lossy();
+                    let order: Vec<_> = value.split(&[',', ';'][..]).map(str::trim).collect();
+                    attr.set_order(&order)?;
+                } else {
+                    attr.set_value(&value)?;
+                }
+                Ok(0)
+            });
+        }
+        Command::Spm {
+            action: SpmAction::Status,
+        } => {
+            print_spm_status(device, output)?;
+        }
+        Command::Commit => {
+            device.commit()?;
+            println!("Settings saved.");
+        }
+        Command::Devices | Command::SignRequest { .. } => {
+            unreachable!("command does not need a device")
+        }
+        Command::Password { .. } | Command::Certificate { .. } => {
+            unreachable!("authentication is managed without unlocking the device")
+        }
+        Command::Diff {
+            format, expected, ..
+        } => {
+            return diff(device.snapshot()?, format, &expected, output);
+        }
+        Command::Export { format, output } => {
+            export_device(device, format, output.as_deref())?;
+        }
+        Command::Apply {
+            format,
+            profile,
+            commit,
+            ..
+        } => {
+            return with_commit_mode(device, commit, || apply_profile(device, format, &profile));
+        }
+        Command::Check {
+            format,
+            report,
+            policy,
+        } => {
+            return check_policy(device, format, report, &policy, output);
+        }
+        Command::Reset { yes, kind } => {
+            reset_device(device, kind.as_deref(), yes, output)?;
         }
         Command::NeedsReboot => {
-            let device = Device::from(&options.device_name);
-            if device.modified()? {
-                println!("true");
+            let reboot_pending = device.modified()?;
+            if output == Output::Text {
+                println!("{}", reboot_pending);
             } else {
-                println!("false");
+                #[derive(Serialize)]
+                struct NeedsReboot {
+                    reboot_pending: bool,
+                }
+                emit(output, &NeedsReboot { reboot_pending })?;
+            }
+            if !reboot_pending {
                 return Ok(1);
             }
         }
@@ -151,28 +497,474 @@ fn run(options: ProgramOptions) -> Result<ReturnCode> {
     Ok(0)
 }
 
-fn device_info(name: &OsStr) -> Result<()> {
-    trace!("printing info for device {:?}", name);
+/// Runs `f` in the commit mode given with `--commit`, saving the staged values
+/// at the end in bulk mode if every value was written.
+fn with_commit_mode(
+    device: &Device,
+    mode: Option<CommitMode>,
+    f: impl FnOnce() -> Result<ReturnCode>,
+) -> Result<ReturnCode> {
+    let mode = match mode {
+        Some(mode) => mode,
+        None => {
+            let result = f();
+            if let Ok(Some(CommitMode::Bulk)) = device.commit_mode() {
+                println!("\nChanges are staged until saved with 'biosctl commit'.");
+            }
+            return result;
+        }
+    };
 
-    println!("Device: {}", name.to_string_lossy());
-    let device = Device::from(name);
+    let previous = device.commit_mode()?;
+    device.set_commit_mode(mode)?;
+    let mut result = f();
+    if mode == CommitMode::Bulk {
+        // A partially applied profile is left staged rather than saved.
+        result = match result {
+            Ok(0) => device.commit().map(|()| 0).map_err(Error::from),
+            result => {
+                eprintln!("\nNothing saved: not every value was written.");
+                result
+            }
+        };
+        if result.is_ok() {
+            println!("Settings saved.");
+        }
+    }
+    if let Some(previous) = previous.filter(|&p| p != mode) {
+        match device.set_commit_mode(previous) {
+            Ok(()) => {}
+            Err(e) if result.is_ok() => return Err(e).context("failed to restore the commit mode"),
+            Err(e) => eprintln!("Failed to restore the commit mode: {}", e),
+        }
+    }
+
+    result
+}
+
+/// Resets the settings of the device, or lists the kinds of reset without `kind`.
+fn reset_device(device: &Device, kind: Option<&str>, yes: bool, output: Output) -> Result<()> {
+    trace!("resetting device {:?} to {:?}", device.name, kind);
+
+    let kind = match kind {
+        Some(k) => k,
+        None => {
+            let kinds = device.reset_kinds()?;
+            if output != Output::Text {
+                return emit(output, &kinds);
+            }
+            for k in kinds {
+                println!("{}", k);
+            }
+            return Ok(());
+        }
+    };
+    if !yes {
+        bail!(
+            "this resets every setting of '{}' to '{}' on the next reboot, add --yes to confirm",
+            device.name.to_string_lossy(),
+            kind
+        );
+    }
+
+    device.reset(kind)?;
+    println!("Settings reset to '{}'.", kind);
+    println!("\nReboot pending: the reset takes effect after a reboot.");
+
+    Ok(())
+}
+
+/// Writes a document in a structured output format.
+fn emit<T: Serialize>(output: Output, value: &T) -> Result<()> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Output::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        Output::Text => unreachable!("text output is written by each command"),
+    }
+    Ok(())
+}
+
+fn list_devices(root: &Path, output: Output) -> Result<()> {
+    trace!("listing devices in {:?}", root);
+
+    #[derive(Serialize)]
+    struct DeviceSummary {
+        name: String,
+        vendor: Option<&'static str>,
+        attributes: usize,
+    }
+
+    let mut devices = Vec::new();
+    for d in Device::enumerate_in(root, Box::new(Sysfs))? {
+        devices.push(DeviceSummary {
+            name: d.name.to_string_lossy().into_owned(),
+            vendor: d.vendor(),
+            attributes: d.attributes()?.count(),
+        });
+    }
+
+    if output != Output::Text {
+        return emit(output, &devices);
+    }
+    for d in devices {
+        println!(
+            "{} ({}): {} attributes",
+            d.name,
+            d.vendor.unwrap_or("Unknown vendor"),
+            d.attributes
+        );
+    }
+
+    Ok(())
+}
+
+fn export_device(device: &Device, format: Option<Format>, output: Option<&Path>) -> Result<()> {
+    trace!("exporting device {:?} to {:?}", device.name, output);
+
+    write_file(&device.snapshot()?, format, output)
+}
+
+/// Writes `value` to `output`, or to stdout.
+fn write_file<T: Serialize>(
+    value: &T,
+    format: Option<Format>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let format = format
+        .or_else(|| output.and_then(Format::from_path))
+        .unwrap_or(Format::Toml);
+    let content = match format {
+        Format::Toml => toml::to_string(value)?,
+        Format::Json => serde_json::to_string_pretty(value)? + "\n",
+    };
+
+    if let Some(output) = output {
+        fs::write(output, content)
+            .with_context(|| format!("failed to write '{}'", output.to_string_lossy()))?;
+    } else {
+        print!("{}", content);
+    }
+
+    Ok(())
+}
+
+fn read_file<T: serde::de::DeserializeOwned>(path: &Path, format: Option<Format>) -> Result<T> {
+    parse_file(&read_text(path)?, path, format)
+}
+
+fn read_text(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("failed to read '{}'", path.to_string_lossy()))
+}
+
+/// Parses the `content` of the file at `path`.
+fn parse_file<T: serde::de::DeserializeOwned>(
+    content: &str,
+    path: &Path,
+    format: Option<Format>,
+) -> Result<T> {
+    let value = match format
+        .or_else(|| Format::from_path(path))
+        .unwrap_or(Format::Toml)
+    {
+        Format::Toml => toml::from_str(content)?,
+        Format::Json => serde_json::from_str(content)?,
+    };
+
+    Ok(value)
+}
+
+fn apply_profile(device: &Device, format: Option<Format>, path: &Path) -> Result<ReturnCode> {
+    trace!("applying profile {:?} to device {:?}", path, device.name);
+
+    let profile: Profile = read_file(path, format)
+        .with_context(|| format!("invalid profile '{}'", path.to_string_lossy()))?;
+    let report = device.apply(&profile)?;
+
+    for (name, outcome) in &report.outcomes {
+        match outcome {
+            Outcome::Unchanged => println!("{}: unchanged", name),
+            Outcome::Changed => println!("{}: changed", name),
+            Outcome::Failed(e) => println!("{}: failed ({})", name, e),
+        }
+    }
+    if report.reboot_pending {
+        println!("\nReboot pending: configuration was modified!");
+    }
+
+    let retcode = report.failed().next().map_or(0, |(_, e)| error_code(e));
+    Ok(retcode)
+}
+
+fn reset_attributes(device: &Device, names: &[OsString], all_modified: bool) -> Result<ReturnCode> {
+    trace!(
+        "resetting attributes {:?} of device {:?}",
+        names,
+        device.name
+    );
 
-    let attributes = device.attributes()?;
-    println!("    {} attributes", attributes.count());
+    let attributes = if all_modified {
+        let mut unknown = Vec::new();
+        let modified: Vec<_> = device
+            .attributes()?
+            .filter(|a| match a.is_modified() {
+                Some(m) => m,
+                None => {
+                    if a.default_value.is_err() {
+                        unknown.push(a.name.to_string_lossy().into_owned());
+                    }
+                    false
+                }
+            })
+            .map(|a| (a.name.clone(), Ok(a)))
+            .collect();
+        if !unknown.is_empty() {
+            println!(
+                "Skipped settings without a readable default value: {}",
+                unknown.join(", ")
+            );
+        }
+        if modified.is_empty() {
+            println!("No setting differs from its default value.");
+        }
+        modified
+    } else {
+        names
+            .iter()
+            .map(|n| (n.clone(), device.attribute(n)))
+            .collect()
+    };
 
+    let mut retcode = 0;
+    for (name, attribute) in attributes {
+        let name = name.to_string_lossy();
+        match attribute.and_then(|mut a| a.restore_default()) {
+            Ok(true) => println!("{}: reset", name),
+            Ok(false) => println!("{}: unchanged", name),
+            Err(e) => {
+                println!("{}: failed ({})", name, e);
+                if retcode == 0 {
+                    retcode = error_code(&e);
+                }
+            }
+        }
+    }
     if device.modified()? {
-        println!("\n    Reboot pending: configuration was modified!");
+        println!("\nReboot pending: configuration was modified!");
     }
 
-    let mut auths = device.authentications()?.peekable();
-    if auths.peek().is_some() {
-        println!("\n    Authentication methods:");
+    Ok(retcode)
+}
+
+fn check_policy(
+    device: &Device,
+    format: Option<Format>,
+    report: Report,
+    path: &Path,
+    output: Output,
+) -> Result<ReturnCode> {
+    trace!(
+        "checking device {:?} against policy {:?}",
+        device.name,
+        path
+    );
+
+    let policy: Policy = read_file(path, format)
+        .with_context(|| format!("invalid policy '{}'", path.to_string_lossy()))?;
+    // An empty policy would pass on any device.
+    if policy.rules.is_empty() {
+        bail!("policy '{}' has no rules", path.to_string_lossy());
+    }
+    let results = device.check(&policy)?;
+    let failures = results.iter().filter(|r| !r.passed).count();
+
+    match report {
+        Report::Text if output != Output::Text => emit(output, &results)?,
+        Report::Text => {
+            for r in &results {
+                let status = if r.passed { "pass" } else { "FAIL" };
+                println!("{}: {} ({})", status, r.rule, r.message);
+            }
+            println!(
+                "\n{} rules, {} passed, {} failed",
+                results.len(),
+                results.len() - failures,
+                failures
+            );
+        }
+        Report::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        Report::Junit => print!("{}", junit_report(device, &results)),
+    }
+
+    Ok(if failures == 0 { 0 } else { 9 })
+}
+
+fn junit_report(device: &Device, results: &[RuleResult]) -> String {
+    let name = xml_escape(&device.name.to_string_lossy());
+    let failures = results.iter().filter(|r| !r.passed).count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+        name,
+        results.len(),
+        failures
+    );
+    for r in results {
+        let rule = xml_escape(&r.rule);
+        if r.passed {
+            xml += &format!("  <testcase classname=\"{}\" name=\"{}\"/>\n", name, rule);
+        } else {
+            xml += &format!(
+                "  <testcase classname=\"{}\" name=\"{}\">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                name,
+                rule,
+                xml_escape(&r.message)
+            );
+        }
+    }
+    xml += "</testsuite>\n";
+
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn sign_request(
+    key: &PrivateKey,
+    format: Option<Format>,
+    snapshot: &Path,
+    profile: &Path,
+    output: Option<&Path>,
+) -> Result<()> {
+    trace!("signing profile {:?} for snapshot {:?}", profile, snapshot);
+
+    let snapshot: Snapshot = read_file(snapshot, format)
+        .with_context(|| format!("invalid snapshot '{}'", snapshot.to_string_lossy()))?;
+    let profile: Profile = read_file(profile, format)
+        .with_context(|| format!("invalid profile '{}'", profile.to_string_lossy()))?;
+    let signatures = Signatures::sign_profile(&snapshot, &profile, key)?;
+
+    write_file(&signatures, format, output)
+}
+
+/// What `diff` compares against.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Expected {
+    Snapshot(Snapshot),
+    Profile(Profile),
+}
+
+fn diff(
+    current: Snapshot,
+    format: Option<Format>,
+    path: &Path,
+    output: Output,
+) -> Result<ReturnCode> {
+    trace!("comparing {:?} with {:?}", current.device, path);
+
+    // Read errors are reported as such, not as a file of the wrong kind.
+    let content = read_text(path)?;
+    let expected: Expected = parse_file(&content, path, format).with_context(|| {
+        format!(
+            "'{}' is neither a profile nor a snapshot",
+            path.to_string_lossy()
+        )
+    })?;
+    let diffs = match expected {
+        Expected::Snapshot(s) => current.diff(&s),
+        Expected::Profile(p) => current.diff_profile(&p),
+    };
+
+    // Differences exit with 8 whatever the output format.
+    if output != Output::Text {
+        emit(output, &diffs)?;
+        return Ok(if diffs.is_empty() { 0 } else { 8 });
+    }
+
+    for d in &diffs {
+        println!("{}", d);
+    }
+
+    if diffs.is_empty() {
+        Ok(0)
     } else {
+        let plural = if diffs.len() == 1 { "" } else { "s" };
+        println!("\n{} difference{}", diffs.len(), plural);
+        Ok(8)
+    }
+}
+
+fn device_info(device: &Device, output: Output) -> Result<()> {
+    let name = &device.name;
+    trace!("printing info for device {:?}", name);
+
+    let (mut attributes, mut with_default, mut modified) = (0, 0, 0);
+    for a in device.attributes()? {
+        attributes += 1;
+        with_default += a.is_modified().is_some() as usize;
+        modified += (a.is_modified() == Some(true)) as usize;
+    }
+    let reboot_pending = device.modified()?;
+    let auths: Vec<_> = device.authentications()?.collect();
+    if auths.is_empty() {
         warn!(
             "no authentications methods found for device '{}'",
             name.to_string_lossy()
         );
     }
+
+    if output != Output::Text {
+        #[derive(Serialize)]
+        struct Info<'a> {
+            device: std::borrow::Cow<'a, str>,
+            vendor: Option<&'static str>,
+            attributes: usize,
+            /// Attributes that differ from their default value.
+            modified: usize,
+            reboot_pending: bool,
+            authentications: Vec<Authentication<'a>>,
+        }
+        return emit(
+            output,
+            &Info {
+                device: name.to_string_lossy(),
+                vendor: device.vendor(),
+                attributes,
+                modified,
+                reboot_pending,
+                authentications: auths,
+            },
+        );
+    }
+
+    println!("Device: {}", name.to_string_lossy());
+    println!("    {} attributes", attributes);
+    // Lenovo devices do not report default values.
+    if with_default > 0 {
+        println!("    {} differ from their default value", modified);
+    }
+
+    if reboot_pending {
+        println!("\n    Reboot pending: configuration was modified!");
+    }
+
+    if !auths.is_empty() {
+        println!("\n    Authentication methods:");
+    }
     for a in auths {
         println!("        {}", a.name.to_string_lossy());
         let role = match a.role {
@@ -184,51 +976,101 @@ fn device_info(name: &OsStr) -> Result<()> {
 
         let status = if a.is_enabled { "Enabled" } else { "Disabled" };
         println!("            Status: {}", status);
+
+        let mechanism = match a.mechanism {
+            Mechanism::Password => "Password".to_string(),
+            Mechanism::Certificate => "Certificate".to_string(),
+            Mechanism::Unknown(m) => format!("Unknown ({})", m),
+        };
+        println!("            Mechanism: {}", mechanism);
+
+        if let Some(level) = a.level {
+            println!("            Level: {}", level);
+        }
+        if let Some(encoding) = a.encoding {
+            println!("            Encoding: {}", encoding);
+        }
+        if let Some(kbdlang) = a.kbdlang {
+            println!("            Keyboard language: {}", kbdlang);
+        }
+        if let Some(index) = a.index {
+            println!("            Index: {}", index);
+        }
     }
 
     Ok(())
 }
 
 fn print_attribute_value(
-    device_name: &OsStr,
+    device: &Device,
     attribute: &OsStr,
     default: bool,
     name: bool,
+    output: Output,
 ) -> Result<(), Error> {
     trace!(
         "printing content of attribute {:?} (device={:?}, default={}, name={})",
         attribute,
-        device_name,
+        device.name,
         default,
         name
     );
 
-    let device = Device::from(device_name);
-    if let Some(a) = device.attribute(attribute)? {
-        if default {
-            if let Ok(d) = a.default_value {
-                println!("{}", d);
-            } else {
-                println!("<Access Denied>");
-            }
-        } else if name {
-            println!("{}", a.display_name);
-        } else if let Ok(d) = a.current_value {
+    let a = device.attribute(attribute)?;
+    if output != Output::Text {
+        #[derive(Serialize)]
+        struct Value<'a> {
+            name: std::borrow::Cow<'a, str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            display_name: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            current_value: Option<ReadValue<'a>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            default_value: Option<ReadValue<'a>>,
+        }
+        let value = Value {
+            name: a.name.to_string_lossy(),
+            display_name: Some(a.display_name.as_str()).filter(|_| name),
+            current_value: Some((&a.current_value).into()).filter(|_| !name && !default),
+            default_value: Some((&a.default_value).into()).filter(|_| default),
+        };
+        emit(output, &value)?;
+    } else if default {
+        if let Ok(d) = a.default_value {
             println!("{}", d);
         } else {
             println!("<Access Denied>");
         }
-        Ok(())
+    } else if name {
+        println!("{}", a.display_name);
+    } else if let Ok(d) = a.current_value {
+        println!("{}", d);
     } else {
-        bail!("no attribute with name {}", attribute.to_string_lossy());
+        println!("<Access Denied>");
     }
+    Ok(())
 }
 
-fn list_device(name: &OsStr) -> Result<()> {
-    trace!("listing attributes in device {:?}", name);
+fn list_device(device: &Device, filter: &Filter, output: Output) -> Result<()> {
+    let name = &device.name;
+    trace!("listing attributes in device {:?} ({:?})", name, filter);
 
-    let device = Device::from(name);
-    let attributes = device.attributes()?;
+    let attributes = select_attributes(device, filter)?;
+    if output != Output::Text {
+        #[derive(Serialize)]
+        struct Entry {
+            name: String,
+            display_name: String,
+        }
+        let attributes: Vec<_> = attributes
+            .into_iter()
+            .map(|a| Entry {
+                name: a.name.to_string_lossy().into_owned(),
+                display_name: a.display_name,
+            })
+            .collect();
+        return emit(output, &DeviceAttributes::new(device, attributes));
+    }
 
     println!("Device: {}\n", name.to_string_lossy());
     for a in attributes {
@@ -238,21 +1080,54 @@ fn list_device(name: &OsStr) -> Result<()> {
     Ok(())
 }
 
-fn print_device(name: &OsStr, attribute: Option<&OsStr>) -> Result<()> {
-    trace!("printing device {:?}", name);
-    let device = Device::from(name);
-    let mut attributes = device.attributes()?;
+/// The attributes of a device, in structured output.
+#[derive(Serialize)]
+struct DeviceAttributes<'a, T> {
+    device: std::borrow::Cow<'a, str>,
+    attributes: Vec<T>,
+}
 
-    if let Some(attribute) = attribute {
-        trace!("filtering by attribute: {:?}", attribute);
-        if let Some(a) = attributes.find(|a| a.name == attribute) {
-            println!("Device: {}\n", name.to_string_lossy());
-            print_attribute(&a)?;
-            return Ok(());
-        } else {
-            bail!("no attribute with name {}", attribute.to_string_lossy());
+impl<'a, T> DeviceAttributes<'a, T> {
+    fn new(device: &'a Device, attributes: Vec<T>) -> Self {
+        DeviceAttributes {
+            device: device.name.to_string_lossy(),
+            attributes,
         }
     }
+}
+
+/// Returns the attributes matching `filter`.
+///
+/// A single setting name is looked up directly, and is an error if no setting
+/// has this name.
+fn select_attributes<'a>(device: &'a Device, filter: &Filter) -> Result<Vec<Attribute<'a>>> {
+    let name = match filter.single_name() {
+        Some(name) => name,
+        None => return Ok(device.attributes()?.filter(|a| filter.matches(a)).collect()),
+    };
+
+    match device.attribute(OsStr::new(name)) {
+        Ok(a) => Ok(vec![a]),
+        Err(e @ biosctl::Error::AttributeNotFound { .. }) => {
+            // Names are matched ignoring case.
+            let attributes: Vec<_> = device.attributes()?.filter(|a| filter.matches(a)).collect();
+            if attributes.is_empty() {
+                return Err(e.into());
+            }
+            Ok(attributes)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn print_device(device: &Device, filter: &Filter, output: Output) -> Result<()> {
+    let name = &device.name;
+    trace!("printing device {:?} ({:?})", name, filter);
+
+    let attributes = select_attributes(device, filter)?;
+    if output != Output::Text {
+        return emit(output, &DeviceAttributes::new(device, attributes));
+    }
 
     println!("Device: {}\n", name.to_string_lossy());
     for a in attributes {
@@ -290,6 +1165,13 @@ fn print_attribute(a: &Attribute) -> Result<()> {
                 writeln!(f, "            {}", p)?;
             }
         }
+        AttributeType::OrderedList { ref elements } => {
+            writeln!(f, "    Type: Ordered List")?;
+            writeln!(f, "        Elements:")?;
+            for e in elements {
+                writeln!(f, "            {}", e)?;
+            }
+        }
     }
     match &a.current_value {
         Ok(v) => {
@@ -307,6 +1189,40 @@ fn print_attribute(a: &Attribute) -> Result<()> {
             writeln!(f, "    Default value: <Access Denied>")?;
         }
     }
+    if !a.constraints.is_empty() {
+        writeln!(f, "    Constraints:")?;
+        for c in &a.constraints {
+            writeln!(f, "        {}", c)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_spm_status(device: &Device, output: Output) -> Result<()> {
+    trace!("printing SPM status of device {:?}", device.name);
+
+    let status = device.spm_status()?;
+    if output != Output::Text {
+        return emit(output, &status);
+    }
+
+    println!("State: {}", status.state);
+    println!("Version: {}", status.version);
+    println!("Nonce: {}", status.nonce);
+    println!("Features in use: {}", status.features_in_use);
+    let provisioned = |key: &str| {
+        if key.is_empty() {
+            "not provisioned"
+        } else {
+            "provisioned"
+        }
+    };
+    println!(
+        "Endorsement key: {}",
+        provisioned(&status.endorsement_key_mod)
+    );
+    println!("Signing key: {}", provisioned(&status.signing_key_mod));
 
     Ok(())
 }
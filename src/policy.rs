@@ -0,0 +1,178 @@
+use crate::{Device, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+
+/// Rules that the configuration of a device must follow.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// A rule of a [`Policy`].
+///
+/// A rule applies either to an `attribute` or to an `authentication` object,
+/// and checks every condition that is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Name of the rule in reports; a description of the rule if missing.
+    pub name: Option<String>,
+    pub attribute: Option<String>,
+    pub authentication: Option<String>,
+    /// Whether the attribute must exist; defaults to true.
+    pub exists: Option<bool>,
+    /// The attribute must have this value.
+    pub equals: Option<String>,
+    /// The attribute must have one of these values.
+    #[serde(rename = "in")]
+    pub one_of: Option<Vec<String>>,
+    /// The attribute must be an integer greater than or equal to this.
+    pub min: Option<i64>,
+    /// The attribute must be an integer lower than or equal to this.
+    pub max: Option<i64>,
+    /// Whether the authentication must be enabled.
+    pub enabled: Option<bool>,
+}
+
+impl Rule {
+    /// Describes what the rule checks.
+    pub fn description(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(false) = self.exists {
+            conditions.push("does not exist".to_string());
+        }
+        if let Some(e) = &self.equals {
+            conditions.push(format!("= '{}'", e));
+        }
+        if let Some(o) = &self.one_of {
+            conditions.push(format!("in [{}]", o.join(", ")));
+        }
+        if let Some(m) = self.min {
+            conditions.push(format!(">= {}", m));
+        }
+        if let Some(m) = self.max {
+            conditions.push(format!("<= {}", m));
+        }
+        if let Some(e) = self.enabled {
+            conditions.push(if e { "enabled" } else { "disabled" }.to_string());
+        }
+        if conditions.is_empty() {
+            conditions.push("exists".to_string());
+        }
+
+        let subject = match (&self.attribute, &self.authentication) {
+            (Some(a), _) => a.clone(),
+            (None, Some(a)) => format!("{} authentication", a),
+            (None, None) => "<nothing>".to_string(),
+        };
+
+        format!("{} {}", subject, conditions.join(" and "))
+    }
+}
+
+/// The result of checking a [`Rule`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleResult {
+    pub rule: String,
+    pub passed: bool,
+    /// What was found on the device.
+    pub message: String,
+}
+
+impl Device {
+    /// Checks every rule of `policy` against the device.
+    pub fn check(&self, policy: &Policy) -> Result<Vec<RuleResult>> {
+        policy
+            .rules
+            .iter()
+            .map(|r| {
+                let (passed, message) = match (&r.attribute, &r.authentication) {
+                    (Some(a), None) => self.check_attribute(r, a)?,
+                    (None, Some(a)) => self.check_authentication(r, a)?,
+                    _ => (
+                        false,
+                        "rule needs exactly one of 'attribute' or 'authentication'".to_string(),
+                    ),
+                };
+
+                Ok(RuleResult {
+                    rule: r.name.clone().unwrap_or_else(|| r.description()),
+                    passed,
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    fn check_attribute(&self, rule: &Rule, name: &str) -> Result<(bool, String)> {
+        let attribute = match self.attribute(OsStr::new(name)) {
+            Ok(a) => a,
+            Err(Error::AttributeNotFound { .. }) => {
+                let passed = rule.exists == Some(false);
+                return Ok((passed, "does not exist".to_string()));
+            }
+            Err(e) => return Err(e),
+        };
+        if rule.exists == Some(false) {
+            return Ok((false, "exists".to_string()));
+        }
+
+        let needs_value = rule.equals.is_some()
+            || rule.one_of.is_some()
+            || rule.min.is_some()
+            || rule.max.is_some();
+        if !needs_value {
+            return Ok((true, "exists".to_string()));
+        }
+        let value = match attribute.current_value {
+            Ok(v) => v,
+            Err(Error::PermissionDenied { .. }) => {
+                return Ok((false, "<Access Denied>".to_string()));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut passed = true;
+        if let Some(e) = &rule.equals {
+            passed &= e.eq_ignore_ascii_case(&value);
+        }
+        if let Some(o) = &rule.one_of {
+            passed &= o.iter().any(|o| o.eq_ignore_ascii_case(&value));
+        }
+        if rule.min.is_some() || rule.max.is_some() {
+            match value.parse::<i64>() {
+                Ok(v) => {
+                    passed &= rule.min.map_or(true, |m| v >= m);
+                    passed &= rule.max.map_or(true, |m| v <= m);
+                }
+                Err(_) => passed = false,
+            }
+        }
+
+        Ok((passed, format!("'{}'", value)))
+    }
+
+    fn check_authentication(&self, rule: &Rule, name: &str) -> Result<(bool, String)> {
+        let auth = match self.authentications()?.find(|a| a.name == name) {
+            Some(a) => a,
+            None => {
+                let passed = rule.exists == Some(false);
+                return Ok((passed, "does not exist".to_string()));
+            }
+        };
+        if rule.exists == Some(false) {
+            return Ok((false, "exists".to_string()));
+        }
+
+        let status = if auth.is_enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        let passed = rule.enabled.map_or(true, |e| e == auth.is_enabled);
+
+        Ok((passed, status.to_string()))
+    }
+}
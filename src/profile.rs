@@ -0,0 +1,479 @@
+use crate::{Attribute, Constraint, Device, Error, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ffi::OsStr, fmt};
+
+/// Values to set on a device, by attribute name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Profile {
+    pub settings: BTreeMap<String, ProfileValue>,
+}
+
+/// A value in a [`Profile`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileValue {
+    String(String),
+    Integer(i64),
+    /// The elements of an ordered list, in order.
+    List(Vec<String>),
+}
+
+impl fmt::Display for ProfileValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileValue::String(s) => write!(f, "{}", s),
+            ProfileValue::Integer(i) => write!(f, "{}", i),
+            ProfileValue::List(l) => write!(f, "{}", l.join(";")),
+        }
+    }
+}
+
+/// What happened to an attribute when applying a profile.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The attribute already had the requested value.
+    Unchanged,
+    Changed,
+    Failed(Error),
+}
+
+/// The result of [`Device::apply`].
+#[derive(Debug)]
+pub struct ApplyReport {
+    pub outcomes: Vec<(String, Outcome)>,
+    /// Whether the device now needs a reboot for changes to take effect.
+    pub reboot_pending: bool,
+}
+
+impl ApplyReport {
+    pub fn failed(&self) -> impl Iterator<Item = (&str, &Error)> {
+        self.outcomes.iter().filter_map(|(n, o)| match o {
+            Outcome::Failed(e) => Some((n.as_str(), e)),
+            _ => None,
+        })
+    }
+}
+
+impl Device {
+    /// Sets every attribute of `profile`.
+    ///
+    /// All attributes are looked up and all values validated before anything
+    /// is written; if any of them is invalid, nothing is written and
+    /// [`Error::InvalidProfile`] lists the problems. Attributes that already
+    /// have the requested value are not written.
+    ///
+    /// Attributes are written after the attributes their constraints depend on,
+    /// e.g. `SecureBoot` before `LegacyOrom`; dependency cycles are reported
+    /// as [`Error::DependencyCycle`]. Constraints are checked against the
+    /// values in effect when each attribute is written, and forced or
+    /// suppressed values also against the values once all are written.
+    ///
+    /// Failed writes are retried as long as other writes succeed, since the
+    /// firmware may have dependencies it does not declare.
+    pub fn apply(&self, profile: &Profile) -> Result<ApplyReport> {
+        let mut errors = Vec::new();
+        let mut pending = Vec::new();
+        for (name, value) in &profile.settings {
+            let attribute = match self.attribute(OsStr::new(name)) {
+                Ok(a) => a,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            match attribute.tpe.validate(&value.to_string()) {
+                Ok(v) => pending.push((attribute, v)),
+                Err(source) => errors.push(Error::InvalidValue {
+                    name: attribute.name,
+                    source,
+                }),
+            }
+        }
+        let order = match write_order(&pending) {
+            Ok(order) => {
+                errors.extend(check_constraints(self, &pending, &order));
+                order
+            }
+            Err(e) => {
+                errors.push(e);
+                Vec::new()
+            }
+        };
+        if !errors.is_empty() {
+            return Err(Error::InvalidProfile { errors });
+        }
+
+        let mut pending: Vec<_> = pending.into_iter().enumerate().collect();
+        pending.sort_by_key(|(i, _)| order.iter().position(|j| j == i));
+        let mut outcomes = Vec::with_capacity(pending.len());
+        let mut failed = Vec::new();
+        for (_, (mut attribute, value)) in pending {
+            let name = attribute.name.to_string_lossy().into_owned();
+            let outcome = if !is_changed(&attribute, &value) {
+                Outcome::Unchanged
+            } else {
+                match attribute.set_value(OsStr::new(&value)) {
+                    Ok(()) => Outcome::Changed,
+                    Err(e) => {
+                        debug!("failed to set '{}', will retry: {}", name, e);
+                        failed.push((outcomes.len(), attribute, value));
+                        Outcome::Failed(e)
+                    }
+                }
+            };
+            outcomes.push((name, outcome));
+        }
+
+        // Retry the failed writes until a round writes nothing more.
+        let mut progress = outcomes.iter().any(|(_, o)| matches!(o, Outcome::Changed));
+        while progress && !failed.is_empty() {
+            let retried = failed.len();
+            let mut still_failed = Vec::new();
+            for (i, mut attribute, value) in failed {
+                outcomes[i].1 = match attribute.set_value(OsStr::new(&value)) {
+                    Ok(()) => Outcome::Changed,
+                    Err(e) => {
+                        still_failed.push((i, attribute, value));
+                        Outcome::Failed(e)
+                    }
+                };
+            }
+            progress = still_failed.len() < retried;
+            failed = still_failed;
+        }
+        for (i, _, _) in &failed {
+            if let (name, Outcome::Failed(e)) = &outcomes[*i] {
+                warn!("failed to set '{}': {}", name, e);
+            }
+        }
+
+        Ok(ApplyReport {
+            outcomes,
+            reboot_pending: self.modified()?,
+        })
+    }
+}
+
+/// Checks the constraints of the changed attributes of `pending`, written
+/// in `order`, against the values in effect when each one is written.
+/// Lasting constraints are also checked against the values in effect once
+/// all of them are written.
+fn check_constraints(
+    device: &Device,
+    pending: &[(Attribute, String)],
+    order: &[usize],
+) -> Vec<Error> {
+    let value_of =
+        |written: &[bool], name: &str| match pending.iter().position(|(a, _)| a.name == name) {
+            Some(j) if written[j] => Some(pending[j].1.clone()),
+            Some(j) => pending[j].0.current_value.as_ref().ok().cloned(),
+            None => device
+                .attribute(OsStr::new(name))
+                .ok()
+                .and_then(|a| a.current_value.ok()),
+        };
+
+    let mut errors = Vec::new();
+    let mut written = vec![false; pending.len()];
+    for &i in order {
+        let (attribute, value) = &pending[i];
+        if is_changed(attribute, value) {
+            if let Err(e) = attribute.check_constraints(value, |n| value_of(&written, n)) {
+                errors.push(e);
+            }
+        }
+        written[i] = true;
+    }
+    for (attribute, value) in pending {
+        if !is_changed(attribute, value) || errors.iter().any(|e| is_about(e, attribute)) {
+            continue;
+        }
+        let result = attribute.check_constraints_where(
+            value,
+            |n| value_of(&written, n),
+            Constraint::is_lasting,
+        );
+        if let Err(e) = result {
+            errors.push(e);
+        }
+    }
+
+    errors
+}
+
+/// Whether `e` is a constraint error of `attribute`.
+fn is_about(e: &Error, attribute: &Attribute) -> bool {
+    matches!(e, Error::ConstraintViolated { name, .. } if *name == attribute.name)
+}
+
+/// Whether writing `value` changes the attribute.
+fn is_changed(attribute: &Attribute, value: &str) -> bool {
+    attribute.current_value.as_deref().ok() != Some(value)
+}
+
+/// Indices of `pending` in the order they must be written, keeping the order
+/// of `pending` where constraints allow.
+///
+/// A constraint of a changed attribute on another changed attribute orders
+/// them only if writing the other one changes whether the constraint is
+/// broken: if it mends it, the other one is written first; if it breaks it,
+/// the other one is written last, unless the constraint is lasting and would
+/// be broken either way.
+fn write_order(pending: &[(Attribute, String)]) -> Result<Vec<usize>> {
+    let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); pending.len()];
+    for (i, (attribute, value)) in pending.iter().enumerate() {
+        if !is_changed(attribute, value) {
+            continue;
+        }
+        for c in &attribute.constraints {
+            let j = match c.condition().and_then(|c| {
+                pending
+                    .iter()
+                    .position(|(a, v)| a.name == c.attribute.as_str() && is_changed(a, v))
+            }) {
+                Some(j) if j != i => j,
+                _ => continue,
+            };
+            let (other, target) = &pending[j];
+            let after = c.is_violated(value, target);
+            let before = match &other.current_value {
+                Ok(current) => c.is_violated(value, current),
+                Err(_) => !after,
+            };
+            match (before, after) {
+                (true, false) => dependencies[i].push(j),
+                (false, true) if !c.is_lasting() => dependencies[j].push(i),
+                _ => {}
+            }
+        }
+    }
+    for deps in &mut dependencies {
+        deps.sort_unstable();
+        deps.dedup();
+    }
+
+    let mut order = Vec::with_capacity(pending.len());
+    let mut written = vec![false; pending.len()];
+    while order.len() < pending.len() {
+        let next = (0..pending.len())
+            .find(|&i| !written[i] && dependencies[i].iter().all(|&j| written[j]));
+        match next {
+            Some(i) => {
+                written[i] = true;
+                order.push(i);
+            }
+            None => return Err(find_cycle(pending, &dependencies, &written)),
+        }
+    }
+
+    Ok(order)
+}
+
+/// Follows the dependencies of the attributes not written yet until one
+/// repeats, which they all have at least one of when none can be written.
+fn find_cycle(
+    pending: &[(Attribute, String)],
+    dependencies: &[Vec<usize>],
+    written: &[bool],
+) -> Error {
+    let mut path: Vec<usize> = Vec::new();
+    let mut i = (0..pending.len()).find(|&i| !written[i]).unwrap_or(0);
+    while !path.contains(&i) {
+        path.push(i);
+        i = dependencies[i]
+            .iter()
+            .copied()
+            .find(|&j| !written[j])
+            .unwrap_or(i);
+    }
+    let start = path.iter().position(|&j| j == i).unwrap_or(0);
+    path.push(i);
+
+    Error::DependencyCycle {
+        names: path[start..]
+            .iter()
+            .map(|&j| pending[j].0.name.clone())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttributeType, Condition};
+    use std::path::Path;
+
+    fn device() -> Device {
+        Device::with_root(Path::new("/nonexistent"), OsStr::new("test"))
+    }
+
+    fn attribute<'a>(device: &'a Device, name: &str, current: &str) -> Attribute<'a> {
+        Attribute {
+            device,
+            name: name.into(),
+            tpe: AttributeType::String {
+                min_length: 0,
+                max_length: 32,
+            },
+            current_value: Ok(current.to_string()),
+            default_value: Err(Error::NoDefaultValue { name: name.into() }),
+            display_name: name.to_string(),
+            display_name_lang: None,
+            constraints: Vec::new(),
+        }
+    }
+
+    fn condition(attribute: &str, value: &str) -> Condition {
+        Condition {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+            equals: true,
+        }
+    }
+
+    fn requires(attribute: &str, value: &str) -> Constraint {
+        Constraint::Requires {
+            condition: condition(attribute, value),
+        }
+    }
+
+    fn read_only_if(attribute: &str, value: &str) -> Constraint {
+        Constraint::ReadOnlyIf {
+            condition: condition(attribute, value),
+        }
+    }
+
+    fn cycle(e: Error) -> Vec<String> {
+        match e {
+            Error::DependencyCycle { names } => names
+                .iter()
+                .map(|n| n.to_string_lossy().into_owned())
+                .collect(),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn write_order_mended_first() {
+        let device = device();
+        let mut orom = attribute(&device, "LegacyOrom", "Disabled");
+        orom.constraints.push(read_only_if("SecureBoot", "Enabled"));
+        let pending = vec![
+            (orom, "Enabled".to_string()),
+            (
+                attribute(&device, "SecureBoot", "Enabled"),
+                "Disabled".to_string(),
+            ),
+        ];
+
+        assert_eq!(write_order(&pending).unwrap(), vec![1, 0]);
+    }
+
+    #[test]
+    fn write_order_broken_last() {
+        let device = device();
+        let mut wake = attribute(&device, "WakeOnLan", "Disabled");
+        wake.constraints.push(read_only_if("DeepSleep", "Enabled"));
+        let pending = vec![
+            (
+                attribute(&device, "DeepSleep", "Disabled"),
+                "Enabled".to_string(),
+            ),
+            (wake, "Enabled".to_string()),
+        ];
+
+        assert_eq!(write_order(&pending).unwrap(), vec![1, 0]);
+    }
+
+    #[test]
+    fn write_order_mutual_references() {
+        // HP declares each of these as a prerequisite of the other.
+        let device = device();
+        let mut secure_boot = attribute(&device, "Secure Boot", "Enable");
+        secure_boot
+            .constraints
+            .push(requires("Legacy Boot Options", "Disable"));
+        let mut legacy = attribute(&device, "Legacy Boot Options", "Disable");
+        legacy.constraints.push(requires("Secure Boot", "Disable"));
+        let pending = vec![
+            (legacy, "Enable".to_string()),
+            (secure_boot, "Disable".to_string()),
+        ];
+
+        let order = write_order(&pending).unwrap();
+        assert_eq!(order, vec![1, 0]);
+        assert!(check_constraints(&device, &pending, &order).is_empty());
+        assert_eq!(check_constraints(&device, &pending, &[0, 1]).len(), 2);
+    }
+
+    #[test]
+    fn write_order_unaffected() {
+        let device = device();
+        let mut a = attribute(&device, "A", "1");
+        a.constraints.push(requires("B", "On"));
+        a.constraints.push(Constraint::ForcedIf {
+            value: "1".to_string(),
+            condition: condition("C", "On"),
+        });
+        let mut b = attribute(&device, "B", "On");
+        b.constraints.push(read_only_if("A", "3"));
+        let pending = vec![
+            (a, "2".to_string()),
+            (b, "on".to_string()),
+            (attribute(&device, "C", "Off"), "On".to_string()),
+        ];
+
+        // B meets the requirement of A before and after it is written, and C
+        // forces A to "1" once written, whatever the order.
+        assert_eq!(write_order(&pending).unwrap(), vec![0, 1, 2]);
+        let errors = check_constraints(&device, &pending, &[0, 1, 2]);
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::ConstraintViolated {
+                constraint: Constraint::ForcedIf { .. },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn write_order_cycle() {
+        let device = device();
+        let mut a = attribute(&device, "A", "Off");
+        a.constraints.push(requires("B", "On"));
+        let mut b = attribute(&device, "B", "Off");
+        b.constraints.push(requires("A", "On"));
+        let pending = vec![
+            (attribute(&device, "C", "Off"), "On".to_string()),
+            (a, "On".to_string()),
+            (b, "On".to_string()),
+        ];
+
+        assert_eq!(cycle(write_order(&pending).unwrap_err()), ["A", "B", "A"]);
+    }
+
+    #[test]
+    fn find_cycle_after_path() {
+        let device = device();
+        let pending: Vec<_> = ["A", "B", "C", "D"]
+            .iter()
+            .map(|n| (attribute(&device, n, "Off"), "On".to_string()))
+            .collect();
+        let dependencies = vec![vec![1], vec![2], vec![0, 3], vec![2]];
+
+        assert_eq!(
+            cycle(find_cycle(&pending, &dependencies, &[false; 4])),
+            ["A", "B", "C", "A"]
+        );
+        assert_eq!(
+            cycle(find_cycle(
+                &pending,
+                &dependencies,
+                &[true, false, false, false]
+            )),
+            ["C", "D", "C"]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{Device, Error, Result};
+use log::*;
+use std::{ffi::OsStr, io};
+
+impl Device {
+    /// Kinds of reset the device supports, e.g. `builtinsafe`, `lastknowngood`,
+    /// `factory` or `custom`.
+    ///
+    /// Returns [`Error::ResetNotSupported`] if the device has no `reset_bios`
+    /// file, which is only available on Dell devices.
+    pub fn reset_kinds(&self) -> Result<Vec<String>> {
+        let kinds = match self.read_value(self.attributes_path(), OsStr::new("reset_bios")) {
+            Ok(k) => k,
+            Err(Error::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::ResetNotSupported {
+                    device: self.name.clone(),
+                })
+            }
+            Err(e) => return Err(e),
+        };
+
+        // The last requested kind is shown in brackets.
+        Ok(kinds
+            .split_whitespace()
+            .map(|k| k.trim_start_matches('[').trim_end_matches(']').to_string())
+            .collect())
+    }
+
+    /// Resets the settings to the configuration `kind`, one of
+    /// [`Device::reset_kinds`].
+    ///
+    /// The settings are reset by the firmware on the next reboot. This needs the
+    /// BIOS admin password to be unlocked if one is set.
+    pub fn reset(&self, kind: &str) -> Result<()> {
+        let kinds = self.reset_kinds()?;
+        let kind = kinds
+            .iter()
+            .find(|k| k.eq_ignore_ascii_case(kind))
+            .ok_or_else(|| Error::InvalidResetKind {
+                kind: kind.to_string(),
+                kinds: kinds.clone(),
+            })?;
+
+        let path = self.attributes_path().join("reset_bios");
+        debug!("writing reset kind {:?} to {:?}", kind, path);
+
+        self.write_value(path, OsStr::new("reset_bios"), kind)
+    }
+}
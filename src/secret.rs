@@ -0,0 +1,88 @@
+use crate::{Error, Result};
+use std::{fmt, fs, path::Path};
+use zeroize::Zeroizing;
+
+/// A password, wiped from memory when dropped.
+///
+/// The value is never shown by `Debug`; use [`Secret::expose`] to get it.
+#[derive(Clone)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    pub fn new(secret: String) -> Secret {
+        Secret(Zeroizing::new(secret))
+    }
+
+    /// Reads a secret from the first line of a file.
+    pub fn from_file(path: &Path) -> Result<Secret> {
+        let mut secret = Zeroizing::new(
+            fs::read_to_string(path).map_err(|e| Error::read(path.to_path_buf(), e))?,
+        );
+        let line = secret.lines().next().map_or(0, str::len);
+        secret.truncate(line);
+
+        Ok(Secret(secret))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Secret::new(secret.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn from_content(content: &str) -> Result<Secret> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        Secret::from_file(file.path())
+    }
+
+    #[test]
+    fn first_line() {
+        assert_eq!(from_content("hunter2").unwrap().expose(), "hunter2");
+        assert_eq!(from_content("hunter2\n").unwrap().expose(), "hunter2");
+        assert_eq!(
+            from_content("hunter2\r\nnext\n").unwrap().expose(),
+            "hunter2"
+        );
+        assert_eq!(
+            from_content(" spaced out \n").unwrap().expose(),
+            " spaced out "
+        );
+        assert_eq!(from_content("").unwrap().expose(), "");
+        assert_eq!(from_content("\nsecond").unwrap().expose(), "");
+    }
+
+    #[test]
+    fn missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing");
+        assert!(matches!(
+            Secret::from_file(&path),
+            Err(Error::Read { path: p, .. }) if p == path
+        ));
+    }
+
+    #[test]
+    fn redacted() {
+        assert_eq!(
+            format!("{:?}", Secret::from("hunter2")),
+            "Secret(<redacted>)"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{AttributeType, Device, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ffi::OsStr, time::SystemTime};
+
+/// The configuration of a device at a point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bios_version: Option<String>,
+    /// RFC 3339 date of the snapshot.
+    pub timestamp: String,
+    pub attributes: BTreeMap<String, AttributeSnapshot>,
+}
+
+/// An attribute in a [`Snapshot`].
+///
+/// Values that could not be read are missing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeSnapshot {
+    pub display_name: String,
+    #[serde(flatten)]
+    pub tpe: AttributeType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<String>,
+}
+
+impl Device {
+    /// Reads every attribute of the device.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let (product, bios_version) = match self.dmi_path() {
+            Some(p) => (
+                self.read_value(p.clone(), OsStr::new("product_name")).ok(),
+                self.read_value(p, OsStr::new("bios_version")).ok(),
+            ),
+            None => (None, None),
+        };
+
+        let attributes = self
+            .attributes()?
+            .map(|a| {
+                (
+                    a.name.to_string_lossy().into_owned(),
+                    AttributeSnapshot {
+                        display_name: a.display_name,
+                        tpe: a.tpe,
+                        current_value: a.current_value.ok(),
+                        default_value: a.default_value.ok(),
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Snapshot {
+            device: self.name.to_string_lossy().into_owned(),
+            product,
+            bios_version,
+            timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            attributes,
+        })
+    }
+}
@@ -0,0 +1,330 @@
+//! Simulated firmware-attributes devices.
+//!
+//! A [`Fixture`] describes a firmware-attributes tree in TOML: tables become
+//! directories and values become files (arrays are joined with `;`, like
+//! `possible_values`, and arrays of integers are binary files of one byte per
+//! integer). A [`Firmware`] materialises a fixture in a temporary
+//! directory and hands out [`Device`]s whose reads and writes follow what the
+//! kernel drivers do:
+//!
+//! * writing an invalid value to `current_value` fails with `EINVAL`, as does
+//!   writing a value that breaks a Dell modifier or an HP prerequisite,
+//! * a successful write to `current_value` sets `pending_reboot` to 1,
+//! * `current_value` cannot be read without privileges, and the password
+//!   files can never be read,
+//! * writing `single` or `bulk` to `save_settings` changes the commit mode,
+//!   and writing `save` is accepted,
+//! * writing a supported kind to `reset_bios` selects it and sets
+//!   `pending_reboot` to 1,
+//! * only `current_value`, `save_settings`, `reset_bios`, the password
+//!   files, the password options and the certificate files are writable,
+//! * writing `new_password` or `certificate` updates `is_enabled` or
+//!   `mechanism` of the authentication object,
+//! * the `level`, `encoding`, `kbdlang` and `index` password options of
+//!   Lenovo authentication objects are writable, and refuse unknown values.
+//!
+//! Recorded fixtures for Dell, Lenovo and HP machines are available as
+//! [`DELL`], [`LENOVO`] and [`HP`].
+
+use crate::{Backend, Constraint, Device};
+use anyhow::{bail, Context, Result};
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+use toml::{value::Table, Value};
+
+/// A `dell-wmi-sysman` device.
+pub const DELL: &str = include_str!("../fixtures/dell-wmi-sysman.toml");
+/// A `thinklmi` device.
+pub const LENOVO: &str = include_str!("../fixtures/thinklmi.toml");
+/// An `hp-bioscfg` device.
+pub const HP: &str = include_str!("../fixtures/hp-bioscfg.toml");
+
+const EACCES: i32 = 13;
+const EINVAL: i32 = 22;
+
+/// A firmware-attributes tree, as read from a fixture file.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    tree: Table,
+}
+
+impl Fixture {
+    pub fn parse(s: &str) -> Result<Fixture> {
+        let tree = toml::from_str(s).context("invalid fixture")?;
+        Ok(Fixture { tree })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Fixture> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read fixture '{}'", path.to_string_lossy()))?;
+        Fixture::parse(&s)
+    }
+
+    /// Names of the devices described by this fixture.
+    pub fn devices(&self) -> impl Iterator<Item = &str> {
+        self.tree.keys().map(String::as_str)
+    }
+
+    /// Merges the devices of `other` into this fixture.
+    pub fn merge(mut self, other: Fixture) -> Fixture {
+        self.tree.extend(other.tree);
+        self
+    }
+}
+
+/// A fixture materialised on disk.
+#[derive(Debug)]
+pub struct Firmware {
+    dir: TempDir,
+    privileged: bool,
+}
+
+impl Firmware {
+    /// Materialises `fixture` in a new temporary directory.
+    ///
+    /// Devices are privileged by default, as if running as root.
+    pub fn new(fixture: &Fixture) -> Result<Firmware> {
+        let dir = tempfile::tempdir().context("failed to create fixture directory")?;
+        materialise(dir.path(), &fixture.tree)?;
+
+        Ok(Firmware {
+            dir,
+            privileged: true,
+        })
+    }
+
+    /// Sets whether devices act as if running as root.
+    pub fn privileged(mut self, privileged: bool) -> Firmware {
+        self.privileged = privileged;
+        self
+    }
+
+    /// Directory standing in for `/sys/class/firmware-attributes`.
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn device(&self, name: &str) -> Device {
+        Device::with_backend(self.root(), OsStr::new(name), self.backend())
+    }
+
+    /// Lists the devices of the fixture, as [`Device::enumerate`] does.
+    pub fn devices(&self) -> Result<Vec<Device>> {
+        Ok(Device::enumerate_in(self.root(), self.backend())?)
+    }
+
+    fn backend(&self) -> Box<dyn Backend> {
+        Box::new(Simulated {
+            privileged: self.privileged,
+        })
+    }
+
+    /// Reads a file of the tree, bypassing the simulated access rules.
+    ///
+    /// `path` is relative to [`root`](Firmware::root).
+    pub fn read(&self, path: impl AsRef<Path>) -> Result<String> {
+        let p = self.root().join(path);
+        let v = fs::read_to_string(&p)
+            .with_context(|| format!("failed to read '{}'", p.to_string_lossy()))?;
+        Ok(v.trim_end().to_string())
+    }
+}
+
+fn materialise(dir: &Path, tree: &Table) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create '{}'", dir.to_string_lossy()))?;
+
+    for (name, value) in tree {
+        let p = dir.join(name);
+        match value {
+            Value::Table(t) => materialise(&p, t)?,
+            v => fs::write(&p, format!("{}\n", file_content(v)?))
+                .with_context(|| format!("failed to write '{}'", p.to_string_lossy()))?,
+        }
+    }
+
+    Ok(())
+}
+
+fn file_content(v: &Value) -> Result<String> {
+    Ok(match v {
+        Value::String(s) => s.clone(),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Boolean(b) => (*b as u8).to_string(),
+        Value::Datetime(d) => d.to_string(),
+        Value::Array(a) => a
+            .iter()
+            .map(file_content)
+            .collect::<Result<Vec<_>>>()?
+            .join(";"),
+        Value::Table(_) => bail!("tables are not allowed in arrays"),
+    })
+}
+
+/// Backend emulating the access rules of the firmware-attributes drivers.
+#[derive(Debug)]
+pub struct Simulated {
+    privileged: bool,
+}
+
+impl Backend for Simulated {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match file_name(path) {
+            "current_password" | "new_password" => Err(io::Error::from_raw_os_error(EACCES)),
+            "current_value" if !self.privileged => Err(io::Error::from_raw_os_error(EACCES)),
+            _ => fs::read_to_string(path),
+        }
+    }
+
+    fn write(&self, path: &Path, value: &[u8]) -> io::Result<()> {
+        if !self.privileged {
+            return Err(io::Error::from_raw_os_error(EACCES));
+        }
+
+        let value = String::from_utf8_lossy(value);
+        let value = value.trim_end_matches('\n');
+
+        match file_name(path) {
+            "current_password" | "signature" | "save_signature" => {
+                fs::write(path, format!("{}\n", value))
+            }
+            "certificate" => {
+                fs::write(path, format!("{}\n", value))?;
+                let mechanism = if value.is_empty() {
+                    "password"
+                } else {
+                    "certificate"
+                };
+                fs::write(parent(path)?.join("mechanism"), format!("{}\n", mechanism))
+            }
+            "new_password" => {
+                fs::write(path, format!("{}\n", value))?;
+                let enabled = !value.is_empty() as u8;
+                fs::write(parent(path)?.join("is_enabled"), format!("{}\n", enabled))
+            }
+            "current_value" => {
+                let attribute = parent(path)?;
+                if !is_valid(attribute, value)? || breaks_constraints(attribute, value)? {
+                    return Err(io::Error::from_raw_os_error(EINVAL));
+                }
+                fs::write(path, format!("{}\n", value))?;
+                fs::write(parent(attribute)?.join("pending_reboot"), "1\n")
+            }
+            "level" | "encoding" | "kbdlang" | "index" => {
+                let valid = match file_name(path) {
+                    "level" => ["user", "master"].contains(&value),
+                    "encoding" => ["ascii", "scancode"].contains(&value),
+                    "kbdlang" => (1..=3).contains(&value.len()),
+                    _ => value.parse::<u32>().is_ok(),
+                };
+                if !valid {
+                    return Err(io::Error::from_raw_os_error(EINVAL));
+                }
+                fs::write(path, format!("{}\n", value))
+            }
+            "save_settings" => match value {
+                "single" | "bulk" => fs::write(path, format!("{}\n", value)),
+                "save" => Ok(()),
+                _ => Err(io::Error::from_raw_os_error(EINVAL)),
+            },
+            "reset_bios" => {
+                let kinds = fs::read_to_string(path)?;
+                let kinds: Vec<_> = kinds
+                    .split_whitespace()
+                    .map(|k| k.trim_start_matches('[').trim_end_matches(']'))
+                    .collect();
+                if !kinds.contains(&value) {
+                    return Err(io::Error::from_raw_os_error(EINVAL));
+                }
+                let kinds: Vec<_> = kinds
+                    .iter()
+                    .map(|&k| {
+                        if k == value {
+                            format!("[{}]", k)
+                        } else {
+                            k.to_string()
+                        }
+                    })
+                    .collect();
+                fs::write(path, format!("{}\n", kinds.join(" ")))?;
+                fs::write(parent(path)?.join("pending_reboot"), "1\n")
+            }
+            _ => Err(io::Error::from_raw_os_error(EACCES)),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(OsStr::to_str).unwrap_or_default()
+}
+
+fn parent(path: &Path) -> io::Result<&Path> {
+    path.parent()
+        .ok_or_else(|| io::Error::from_raw_os_error(EINVAL))
+}
+
+fn breaks_constraints(attribute: &Path, value: &str) -> io::Result<bool> {
+    let constraints = Constraint::read_all(|name| {
+        fs::read_to_string(attribute.join(name))
+            .ok()
+            .map(|v| v.trim_end().to_string())
+    });
+
+    let attributes = parent(attribute)?;
+    Ok(constraints.iter().any(|c| {
+        c.condition()
+            .and_then(|c| {
+                fs::read_to_string(attributes.join(&c.attribute).join("current_value")).ok()
+            })
+            .map_or(false, |other| c.is_violated(value, other.trim_end()))
+    }))
+}
+
+fn is_valid(attribute: &Path, value: &str) -> io::Result<bool> {
+    let read = |name: &str| -> io::Result<String> {
+        let p: PathBuf = attribute.join(name);
+        Ok(fs::read_to_string(p)?.trim_end().to_string())
+    };
+    let number = |name: &str| -> io::Result<i64> {
+        read(name)?
+            .parse()
+            .map_err(|_| io::Error::from_raw_os_error(EINVAL))
+    };
+
+    Ok(match read("type")?.as_ref() {
+        "enumeration" => read("possible_values")?
+            .split(';')
+            .any(|p| !p.is_empty() && p.eq_ignore_ascii_case(value)),
+        "integer" => match value.parse::<i64>() {
+            Ok(v) => {
+                let min = number("min_value")?;
+                let max = number("max_value")?;
+                let step = number("scalar_increment")?.max(1);
+                let offset = i128::from(v) - i128::from(min);
+                (min..=max).contains(&v) && offset.rem_euclid(i128::from(step)) == 0
+            }
+            Err(_) => false,
+        },
+        "string" => {
+            let len = value.chars().count() as i64;
+            (number("min_length")?..=number("max_length")?).contains(&len)
+        }
+        "ordered-list" => {
+            let mut elements: Vec<_> = read("elements")?
+                .split(';')
+                .filter(|e| !e.is_empty())
+                .map(str::to_string)
+                .collect();
+            let mut order: Vec<_> = value.split(';').map(str::to_string).collect();
+            elements.sort();
+            order.sort();
+            elements == order
+        }
+        _ => true,
+    })
+}
@@ -0,0 +1,333 @@
+use crate::AttributeType;
+use std::fmt;
+
+/// A value that does not fit the constraints of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    NotPossibleValue {
+        value: String,
+        possible_values: Vec<String>,
+        suggestion: Option<String>,
+    },
+    NotAnInteger {
+        value: String,
+    },
+    OutOfRange {
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    NotAStep {
+        value: i64,
+        min: i64,
+        step: u64,
+    },
+    BadLength {
+        length: u64,
+        min_length: u64,
+        max_length: u64,
+    },
+    UnknownElement {
+        element: String,
+    },
+    DuplicateElement {
+        element: String,
+    },
+    MissingElement {
+        element: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NotPossibleValue {
+                value,
+                possible_values,
+                suggestion,
+            } => {
+                write!(
+                    f,
+                    "'{}' is not a possible value, expected one of: {}",
+                    value,
+                    possible_values.join(", ")
+                )?;
+                if let Some(s) = suggestion {
+                    write!(f, " (did you mean '{}'?)", s)?;
+                }
+                Ok(())
+            }
+            ValidationError::NotAnInteger { value } => write!(f, "'{}' is not an integer", value),
+            ValidationError::OutOfRange { value, min, max } => write!(
+                f,
+                "{} is out of range, expected a value between {} and {}",
+                value, min, max
+            ),
+            ValidationError::NotAStep { value, min, step } => write!(
+                f,
+                "{} is not a valid value, expected {} plus a multiple of {}",
+                value, min, step
+            ),
+            ValidationError::BadLength {
+                length,
+                min_length,
+                max_length,
+            } => write!(
+                f,
+                "value is {} characters long, expected between {} and {}",
+                length, min_length, max_length
+            ),
+            ValidationError::UnknownElement { element } => {
+                write!(f, "'{}' is not an element of the list", element)
+            }
+            ValidationError::DuplicateElement { element } => {
+                write!(f, "'{}' is listed more than once", element)
+            }
+            ValidationError::MissingElement { element } => {
+                write!(f, "'{}' is missing from the list", element)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl AttributeType {
+    /// Checks that `value` can be written to an attribute of this type.
+    ///
+    /// Returns the value as it should be written: possible values of
+    /// enumerations are matched case-insensitively and spelled as the
+    /// firmware expects.
+    pub fn validate(&self, value: &str) -> Result<String, ValidationError> {
+        match self {
+            AttributeType::Enumeration { possible_values } => {
+                if let Some(p) = possible_values
+                    .iter()
+                    .find(|p| p.eq_ignore_ascii_case(value))
+                {
+                    return Ok(p.clone());
+                }
+
+                let lower = value.to_lowercase();
+                let suggestion = possible_values
+                    .iter()
+                    .map(|p| (strsim::levenshtein(&lower, &p.to_lowercase()), p))
+                    .filter(|(d, p)| *d <= (p.len() / 3).max(2))
+                    .min_by_key(|(d, _)| *d)
+                    .map(|(_, p)| p.clone());
+
+                Err(ValidationError::NotPossibleValue {
+                    value: value.to_string(),
+                    possible_values: possible_values.clone(),
+                    suggestion,
+                })
+            }
+            AttributeType::Integer { min, max, step } => {
+                let v: i64 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| ValidationError::NotAnInteger {
+                        value: value.to_string(),
+                    })?;
+                if v < *min || v > *max {
+                    return Err(ValidationError::OutOfRange {
+                        value: v,
+                        min: *min,
+                        max: *max,
+                    });
+                }
+                // In i128, so that bounds near i64::MIN and i64::MAX do not overflow.
+                let offset = i128::from(v) - i128::from(*min);
+                if offset.rem_euclid(i128::from((*step).max(1))) != 0 {
+                    return Err(ValidationError::NotAStep {
+                        value: v,
+                        min: *min,
+                        step: *step,
+                    });
+                }
+
+                Ok(v.to_string())
+            }
+            AttributeType::String {
+                min_length,
+                max_length,
+            } => {
+                let length = value.chars().count() as u64;
+                if length < *min_length || length > *max_length {
+                    return Err(ValidationError::BadLength {
+                        length,
+                        min_length: *min_length,
+                        max_length: *max_length,
+                    });
+                }
+
+                Ok(value.to_string())
+            }
+            AttributeType::OrderedList { elements } => {
+                let mut seen: Vec<&str> = Vec::with_capacity(elements.len());
+                for o in value.split(';') {
+                    if !elements.iter().any(|e| e == o) {
+                        return Err(ValidationError::UnknownElement {
+                            element: o.to_string(),
+                        });
+                    }
+                    if seen.contains(&o) {
+                        return Err(ValidationError::DuplicateElement {
+                            element: o.to_string(),
+                        });
+                    }
+                    seen.push(o);
+                }
+                if let Some(missing) = elements.iter().find(|e| !seen.contains(&e.as_str())) {
+                    return Err(ValidationError::MissingElement {
+                        element: missing.clone(),
+                    });
+                }
+
+                Ok(value.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(min: i64, max: i64, step: u64) -> AttributeType {
+        AttributeType::Integer { min, max, step }
+    }
+
+    #[test]
+    fn integer_in_range() {
+        let t = integer(0, 23, 1);
+        assert_eq!(t.validate("0"), Ok("0".to_string()));
+        assert_eq!(t.validate(" 23 "), Ok("23".to_string()));
+        assert_eq!(
+            t.validate("24"),
+            Err(ValidationError::OutOfRange {
+                value: 24,
+                min: 0,
+                max: 23
+            })
+        );
+        assert_eq!(
+            t.validate("-1"),
+            Err(ValidationError::OutOfRange {
+                value: -1,
+                min: 0,
+                max: 23
+            })
+        );
+        assert_eq!(
+            t.validate("six"),
+            Err(ValidationError::NotAnInteger {
+                value: "six".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn integer_step() {
+        let t = integer(-5, 20, 5);
+        assert_eq!(t.validate("-5"), Ok("-5".to_string()));
+        assert_eq!(t.validate("15"), Ok("15".to_string()));
+        assert_eq!(
+            t.validate("12"),
+            Err(ValidationError::NotAStep {
+                value: 12,
+                min: -5,
+                step: 5
+            })
+        );
+        // A step of 0 accepts every value.
+        assert_eq!(integer(0, 10, 0).validate("7"), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn integer_bounds() {
+        let t = integer(i64::MIN, i64::MAX, 1);
+        assert_eq!(t.validate(&i64::MIN.to_string()), Ok(i64::MIN.to_string()));
+        assert_eq!(t.validate(&i64::MAX.to_string()), Ok(i64::MAX.to_string()));
+
+        let t = integer(i64::MIN, i64::MAX, 2);
+        assert_eq!(t.validate(&i64::MAX.to_string()).ok(), None);
+        assert_eq!(t.validate("0"), Ok("0".to_string()));
+        assert_eq!(
+            t.validate(&(i64::MAX - 1).to_string()),
+            Ok((i64::MAX - 1).to_string())
+        );
+
+        let t = integer(i64::MAX - 10, i64::MAX, u64::MAX);
+        assert_eq!(
+            t.validate(&(i64::MAX - 10).to_string()),
+            Ok((i64::MAX - 10).to_string())
+        );
+        assert!(t.validate(&i64::MAX.to_string()).is_err());
+    }
+
+    #[test]
+    fn enumeration() {
+        let t = AttributeType::Enumeration {
+            possible_values: vec!["Enabled".to_string(), "Disabled".to_string()],
+        };
+        assert_eq!(t.validate("enabled"), Ok("Enabled".to_string()));
+        match t.validate("Enabeld") {
+            Err(ValidationError::NotPossibleValue { suggestion, .. }) => {
+                assert_eq!(suggestion.as_deref(), Some("Enabled"))
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match t.validate("Auto") {
+            Err(ValidationError::NotPossibleValue { suggestion, .. }) => {
+                assert_eq!(suggestion, None)
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn string_length() {
+        let t = AttributeType::String {
+            min_length: 1,
+            max_length: 4,
+        };
+        assert_eq!(t.validate("ab"), Ok("ab".to_string()));
+        // Characters are counted, not bytes.
+        assert_eq!(t.validate("éééé"), Ok("éééé".to_string()));
+        assert_eq!(
+            t.validate(""),
+            Err(ValidationError::BadLength {
+                length: 0,
+                min_length: 1,
+                max_length: 4
+            })
+        );
+    }
+
+    #[test]
+    fn ordered_list() {
+        let t = AttributeType::OrderedList {
+            elements: vec!["USB".to_string(), "HDD".to_string(), "NET".to_string()],
+        };
+        assert_eq!(t.validate("NET;USB;HDD"), Ok("NET;USB;HDD".to_string()));
+        assert_eq!(
+            t.validate("NET;USB"),
+            Err(ValidationError::MissingElement {
+                element: "HDD".to_string()
+            })
+        );
+        assert_eq!(
+            t.validate("NET;USB;USB;HDD"),
+            Err(ValidationError::DuplicateElement {
+                element: "USB".to_string()
+            })
+        );
+        assert_eq!(
+            t.validate("NET;USB;CD"),
+            Err(ValidationError::UnknownElement {
+                element: "CD".to_string()
+            })
+        );
+    }
+}
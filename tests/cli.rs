@@ -0,0 +1,668 @@
+use biosctl::testing::{Firmware, Fixture, DELL, HP, LENOVO};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+use tempfile::TempDir;
+
+const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/test-key.pem");
+
+/// A simulated machine, and a directory for the files given to biosctl.
+struct Machine {
+    firmware: Firmware,
+    dir: TempDir,
+}
+
+/// What biosctl returned.
+struct Run {
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+impl Machine {
+    fn new(fixture: &str) -> Machine {
+        Machine::with(Fixture::parse(fixture).unwrap())
+    }
+
+    fn with(fixture: Fixture) -> Machine {
+        Machine {
+            firmware: Firmware::new(&fixture).unwrap(),
+            dir: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn biosctl(&self, args: &[&str]) -> Run {
+        let output = process::Command::new(env!("CARGO_BIN_EXE_biosctl"))
+            .arg("--sysfs-root")
+            .arg(self.firmware.root())
+            .args(args)
+            .current_dir(self.dir.path())
+            .env_remove("BIOSCTL_PASSWORD")
+            .env_remove("BIOSCTL_SYSFS_ROOT")
+            .env_remove("BIOSCTL_LOG")
+            .output()
+            .unwrap();
+
+        Run {
+            code: output.status.code().unwrap(),
+            stdout: String::from_utf8(output.stdout).unwrap(),
+            stderr: String::from_utf8(output.stderr).unwrap(),
+        }
+    }
+
+    /// Runs biosctl, expecting it to exit with `code`.
+    fn expect(&self, code: i32, args: &[&str]) -> Run {
+        let run = self.biosctl(args);
+        assert_eq!(
+            run.code, code,
+            "biosctl {:?}\nstdout:\n{}\nstderr:\n{}",
+            args, run.stdout, run.stderr
+        );
+        run
+    }
+
+    /// Writes a file in the directory biosctl runs in.
+    fn write(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn value(&self, path: impl AsRef<Path>) -> String {
+        self.firmware.read(path).unwrap()
+    }
+
+    /// Writes a file of the tree, standing in for the firmware: biosctl reads
+    /// and writes the tree as plain files, so the firmware does not react to
+    /// what it writes.
+    fn firmware_writes(&self, path: &str, value: &str) {
+        fs::write(self.firmware.root().join(path), format!("{}\n", value)).unwrap();
+    }
+}
+
+#[test]
+fn devices() {
+    let machine = Machine::new(DELL);
+    let run = machine.expect(0, &["devices"]);
+    assert_eq!(run.stdout.lines().count(), 1, "{}", run.stdout);
+    assert!(run.stdout.contains("dell-wmi-sysman"), "{}", run.stdout);
+    machine.expect(0, &["list"]);
+
+    let machine = Machine::with(
+        Fixture::parse(DELL)
+            .unwrap()
+            .merge(Fixture::parse(LENOVO).unwrap()),
+    );
+    let run = machine.expect(0, &["devices"]);
+    assert!(run.stdout.contains("dell-wmi-sysman"), "{}", run.stdout);
+    assert!(run.stdout.contains("thinklmi"), "{}", run.stdout);
+
+    // Several devices, none selected.
+    machine.expect(2, &["list"]);
+    machine.expect(0, &["-D", "thinklmi", "list"]);
+}
+
+#[test]
+fn set_order() {
+    let machine = Machine::new(HP);
+
+    machine.expect(
+        0,
+        &[
+            "set",
+            "UEFI Boot Order",
+            "USB:1, HDD:M.2:1, NETWORK IPV4:EMBEDDED:1, NETWORK IPV6:EMBEDDED:1",
+        ],
+    );
+    assert_eq!(
+        machine.value("hp-bioscfg/attributes/UEFI Boot Order/current_value"),
+        "USB:1;HDD:M.2:1;NETWORK IPV4:EMBEDDED:1;NETWORK IPV6:EMBEDDED:1"
+    );
+    machine.expect(5, &["set", "UEFI Boot Order", "USB:1"]);
+}
+
+#[test]
+fn set() {
+    let machine = Machine::new(DELL);
+
+    machine.expect(0, &["set", "WakeOnAc", "enabled"]);
+    assert_eq!(
+        machine.value("dell-wmi-sysman/attributes/WakeOnAc/current_value"),
+        "Enabled"
+    );
+
+    let run = machine.expect(5, &["set", "FanSpeedLvl", "101"]);
+    assert!(run.stderr.contains("FanSpeedLvl"), "{}", run.stderr);
+    assert_eq!(
+        run.stderr.matches("out of range").count(),
+        1,
+        "{}",
+        run.stderr
+    );
+    assert_eq!(
+        machine.value("dell-wmi-sysman/attributes/FanSpeedLvl/current_value"),
+        "50"
+    );
+}
+
+#[test]
+fn get() {
+    let machine = Machine::new(DELL);
+
+    assert_eq!(machine.expect(0, &["get", "FanSpeedLvl"]).stdout, "50\n");
+    assert_eq!(
+        machine.expect(0, &["get", "--default", "WakeOnAc"]).stdout,
+        "Disabled\n"
+    );
+
+    let run = machine.expect(3, &["get", "Missing"]);
+    assert!(run.stdout.is_empty(), "{}", run.stdout);
+    assert!(
+        run.stderr.contains("no setting with name 'Missing'"),
+        "{}",
+        run.stderr
+    );
+    machine.expect(3, &["set", "Missing", "1"]);
+}
+
+#[test]
+fn export() {
+    let machine = Machine::new(DELL);
+
+    machine.expect(0, &["export", "snapshot.toml"]);
+    machine.expect(0, &["export", "snapshot.json"]);
+    for name in &["snapshot.toml", "snapshot.json"] {
+        let snapshot = fs::read_to_string(machine.dir.path().join(name)).unwrap();
+        assert!(snapshot.contains("dell-wmi-sysman"), "{}", snapshot);
+        assert!(snapshot.contains("WakeOnAc"), "{}", snapshot);
+    }
+}
+
+#[test]
+fn apply() {
+    let machine = Machine::new(DELL);
+
+    machine.write("profile.toml", "WakeOnAc = \"Enabled\"\nFanSpeedLvl = 50\n");
+    let run = machine.expect(0, &["apply", "profile.toml"]);
+    assert!(run.stdout.contains("WakeOnAc: changed"), "{}", run.stdout);
+    assert!(
+        run.stdout.contains("FanSpeedLvl: unchanged"),
+        "{}",
+        run.stdout
+    );
+
+    machine.write(
+        "invalid.json",
+        r#"{"FanSpeedLvl": 101, "WakeOnDock": "Disabled"}"#,
+    );
+    machine.expect(5, &["apply", "invalid.json"]);
+    assert_eq!(
+        machine.value("dell-wmi-sysman/attributes/WakeOnDock/current_value"),
+        "Enabled"
+    );
+}
+
+#[test]
+fn diff() {
+    let machine = Machine::new(DELL);
+
+    machine.expect(0, &["export", "snapshot.toml"]);
+    machine.expect(0, &["export", "snapshot.json"]);
+    machine.expect(0, &["diff", "snapshot.toml"]);
+
+    machine.expect(0, &["set", "WakeOnAc", "Enabled"]);
+    let run = machine.expect(8, &["diff", "snapshot.json"]);
+    assert!(run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+
+    machine.expect(0, &["export", "changed.toml"]);
+    machine.expect(0, &["diff", "changed.toml"]);
+    machine.expect(8, &["diff", "snapshot.toml", "changed.toml"]);
+
+    machine.write("profile.toml", "WakeOnAc = \"Enabled\"\n");
+    machine.expect(0, &["diff", "profile.toml"]);
+    machine.write("other.toml", "WakeOnAc = \"Disabled\"\n");
+    machine.expect(8, &["diff", "other.toml"]);
+}
+
+#[test]
+fn check() {
+    let machine = Machine::new(DELL);
+
+    machine.write(
+        "pass.toml",
+        r#"
+[[rule]]
+attribute = "SecureBoot"
+equals = "Enabled"
+
+[[rule]]
+attribute = "FanSpeedLvl"
+min = 20
+max = 80
+
+[[rule]]
+authentication = "Admin"
+enabled = false
+"#,
+    );
+    machine.expect(0, &["check", "pass.toml"]);
+
+    machine.write(
+        "fail.toml",
+        r#"
+[[rule]]
+name = "Wake on AC"
+attribute = "WakeOnAc"
+equals = "Enabled"
+"#,
+    );
+    let run = machine.expect(9, &["check", "fail.toml"]);
+    assert!(run.stdout.contains("Wake on AC"), "{}", run.stdout);
+    let run = machine.expect(9, &["check", "--report", "junit", "fail.toml"]);
+    assert!(run.stdout.contains("<testsuite"), "{}", run.stdout);
+    let run = machine.expect(9, &["check", "--report", "json", "fail.toml"]);
+    serde_json::from_str::<serde_json::Value>(&run.stdout).unwrap();
+
+    machine.write("empty.toml", "");
+    machine.expect(1, &["check", "empty.toml"]);
+    machine.write("unknown.toml", "[[rules]]\nattribute = \"WakeOnAc\"\n");
+    machine.expect(1, &["check", "unknown.toml"]);
+}
+
+#[test]
+fn structured_output() {
+    let machine = Machine::new(DELL);
+
+    let run = machine.expect(0, &["-o", "json", "devices"]);
+    let devices: serde_json::Value = serde_json::from_str(&run.stdout).unwrap();
+    assert_eq!(devices.as_array().map(Vec::len), Some(1));
+
+    let run = machine.expect(0, &["-o", "json", "get", "WakeOnAc"]);
+    serde_json::from_str::<serde_json::Value>(&run.stdout).unwrap();
+    let run = machine.expect(0, &["-o", "json", "list"]);
+    serde_json::from_str::<serde_json::Value>(&run.stdout).unwrap();
+    let run = machine.expect(0, &["-o", "yaml", "print", "WakeOnAc"]);
+    assert!(run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+    let run = machine.expect(0, &["-o", "yaml", "info"]);
+    assert!(run.stdout.contains("Admin"), "{}", run.stdout);
+}
+
+#[test]
+fn needs_reboot() {
+    let machine = Machine::new(DELL);
+
+    assert_eq!(machine.expect(1, &["needs-reboot"]).stdout, "false\n");
+    machine.firmware_writes("dell-wmi-sysman/attributes/pending_reboot", "1");
+    assert_eq!(machine.expect(0, &["needs-reboot"]).stdout, "true\n");
+    let run = machine.expect(0, &["-o", "json", "needs-reboot"]);
+    assert!(
+        run.stdout.contains("\"reboot_pending\": true"),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn auth() {
+    let machine = Machine::new(DELL);
+
+    machine.write("password", "hunter2\n");
+    let run = machine.expect(
+        0,
+        &[
+            "--auth",
+            "System",
+            "--password-file",
+            "password",
+            "set",
+            "WakeOnAc",
+            "Enabled",
+        ],
+    );
+    assert!(run.stderr.contains("BIOS unlocked"), "{}", run.stderr);
+    assert_eq!(
+        machine.value("dell-wmi-sysman/authentication/System/current_password"),
+        ""
+    );
+    machine.expect(
+        3,
+        &["--auth", "Missing", "--password-file", "password", "list"],
+    );
+}
+
+#[test]
+fn password() {
+    let machine = Machine::new(DELL);
+    let admin = "dell-wmi-sysman/authentication/Admin";
+
+    machine.write("short", "abc\n");
+    machine.write("first", "hunter2\n");
+    machine.write("second", "hunter3\n");
+
+    machine.expect(5, &["password", "set", "--new-password-file", "short"]);
+    machine.expect(0, &["password", "set", "--new-password-file", "first"]);
+    assert_eq!(machine.value(format!("{}/new_password", admin)), "hunter2");
+    machine.firmware_writes(&format!("{}/is_enabled", admin), "1");
+    machine.expect(1, &["password", "set", "--new-password-file", "first"]);
+
+    let run = machine.expect(
+        0,
+        &["--password-file", "first", "set", "WakeOnAc", "Enabled"],
+    );
+    assert!(run.stderr.contains("BIOS unlocked"), "{}", run.stderr);
+    assert_eq!(machine.value(format!("{}/current_password", admin)), "");
+
+    machine.expect(
+        0,
+        &[
+            "--password-file",
+            "first",
+            "password",
+            "change",
+            "--new-password-file",
+            "second",
+        ],
+    );
+    machine.expect(4, &["password", "clear"]);
+    machine.expect(0, &["--password-file", "second", "password", "clear"]);
+    assert_eq!(machine.value(format!("{}/new_password", admin)), "");
+}
+
+#[test]
+fn certificate() {
+    let machine = Machine::new(LENOVO);
+    let admin = "thinklmi/authentication/Admin";
+
+    machine.write("password", "hunter2\n");
+    machine.write(
+        "certificate.pem",
+        "-----BEGIN CERTIFICATE-----\nMIIB\nAAAA\n-----END CERTIFICATE-----\n",
+    );
+    machine.expect(0, &["password", "set", "--new-password-file", "password"]);
+    machine.firmware_writes(&format!("{}/is_enabled", admin), "1");
+
+    machine.expect(4, &["certificate", "install", "certificate.pem"]);
+    machine.expect(
+        0,
+        &[
+            "--password-file",
+            "password",
+            "certificate",
+            "install",
+            "certificate.pem",
+        ],
+    );
+    assert_eq!(machine.value(format!("{}/certificate", admin)), "MIIBAAAA");
+    machine.firmware_writes(&format!("{}/mechanism", admin), "certificate");
+
+    machine.expect(0, &["--private-key", KEY, "set", "WakeOnLAN", "Disable"]);
+    assert!(!machine.value(format!("{}/signature", admin)).is_empty());
+
+    machine.expect(1, &["certificate", "replace", "certificate.pem"]);
+    machine.expect(
+        0,
+        &[
+            "--private-key",
+            KEY,
+            "certificate",
+            "replace",
+            "certificate.pem",
+        ],
+    );
+    machine.expect(0, &["--private-key", KEY, "certificate", "clear"]);
+    assert_eq!(machine.value(format!("{}/certificate", admin)), "");
+}
+
+#[test]
+fn signatures() {
+    let machine = Machine::new(LENOVO);
+    let admin = "thinklmi/authentication/Admin";
+    machine.firmware_writes(&format!("{}/is_enabled", admin), "1");
+    machine.firmware_writes(&format!("{}/mechanism", admin), "certificate");
+
+    // Signatures made in advance, away from the machine.
+    machine.expect(0, &["export", "snapshot.toml"]);
+    machine.write("profile.toml", "WakeOnLAN = \"Disable\"\n");
+    machine.expect(
+        0,
+        &[
+            "--private-key",
+            KEY,
+            "sign-request",
+            "--snapshot",
+            "snapshot.toml",
+            "profile.toml",
+            "signatures.toml",
+        ],
+    );
+    machine.expect(
+        0,
+        &["apply", "--signatures", "signatures.toml", "profile.toml"],
+    );
+    assert_eq!(
+        machine.value("thinklmi/attributes/WakeOnLAN/current_value"),
+        "Disable"
+    );
+    assert!(!machine.value(format!("{}/signature", admin)).is_empty());
+
+    machine.write("other.toml", "WakeOnLAN = \"Enable\"\n");
+    machine.expect(
+        4,
+        &["apply", "--signatures", "signatures.toml", "other.toml"],
+    );
+    machine.expect(
+        1,
+        &[
+            "--private-key",
+            KEY,
+            "apply",
+            "--signatures",
+            "signatures.toml",
+            "profile.toml",
+        ],
+    );
+}
+
+#[test]
+fn reset() {
+    let machine = Machine::new(DELL);
+
+    let run = machine.expect(0, &["reset"]);
+    assert_eq!(run.stdout, "builtinsafe\nlastknowngood\nfactory\ncustom\n");
+
+    machine.expect(1, &["reset", "factory"]);
+    machine.expect(5, &["reset", "--yes", "everything"]);
+    machine.expect(0, &["reset", "--yes", "factory"]);
+    assert_eq!(
+        machine.value("dell-wmi-sysman/attributes/reset_bios"),
+        "factory"
+    );
+
+    Machine::new(LENOVO).expect(3, &["reset"]);
+}
+
+#[test]
+fn reset_attr() {
+    let machine = Machine::new(DELL);
+    let path = "dell-wmi-sysman/attributes/WakeOnAc/current_value";
+
+    machine.expect(0, &["set", "WakeOnAc", "Enabled"]);
+    let run = machine.expect(0, &["reset-attr", "WakeOnAc", "FanSpeedLvl"]);
+    assert!(run.stdout.contains("WakeOnAc: reset"), "{}", run.stdout);
+    assert!(
+        run.stdout.contains("FanSpeedLvl: unchanged"),
+        "{}",
+        run.stdout
+    );
+    assert_eq!(machine.value(path), "Disabled");
+
+    machine.expect(0, &["set", "WakeOnAc", "Enabled"]);
+    machine.expect(0, &["set", "FanSpeedLvl", "60"]);
+    let run = machine.expect(0, &["reset-attr", "--all-modified"]);
+    assert!(run.stdout.contains("WakeOnAc: reset"), "{}", run.stdout);
+    assert!(run.stdout.contains("FanSpeedLvl: reset"), "{}", run.stdout);
+    assert_eq!(machine.value(path), "Disabled");
+
+    let run = machine.expect(0, &["reset-attr", "--all-modified"]);
+    assert!(
+        run.stdout
+            .contains("No setting differs from its default value."),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn modified() {
+    let machine = Machine::new(DELL);
+
+    let run = machine.expect(0, &["list", "--modified"]);
+    assert!(!run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+    let run = machine.expect(0, &["info"]);
+    assert!(
+        run.stdout.contains("0 differ from their default value"),
+        "{}",
+        run.stdout
+    );
+
+    machine.expect(0, &["set", "WakeOnAc", "Enabled"]);
+    let run = machine.expect(0, &["list", "--modified"]);
+    assert!(run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+    assert!(!run.stdout.contains("FanSpeedLvl"), "{}", run.stdout);
+    let run = machine.expect(0, &["print", "--modified"]);
+    assert!(run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+    let run = machine.expect(0, &["info"]);
+    assert!(
+        run.stdout.contains("1 differ from their default value"),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn filter() {
+    let machine = Machine::new(DELL);
+
+    let run = machine.expect(0, &["list"]);
+    assert!(run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+    assert!(run.stdout.contains("FanSpeedLvl"), "{}", run.stdout);
+
+    let run = machine.expect(0, &["list", "Wake*"]);
+    assert!(run.stdout.contains("WakeOnDock"), "{}", run.stdout);
+    assert!(!run.stdout.contains("FanSpeedLvl"), "{}", run.stdout);
+
+    let run = machine.expect(0, &["list", "--type", "integer"]);
+    assert!(run.stdout.contains("AutoOnHr"), "{}", run.stdout);
+    assert!(!run.stdout.contains("WakeOnAc"), "{}", run.stdout);
+}
+
+#[test]
+fn lenovo_password_options() {
+    let machine = Machine::new(LENOVO);
+    let admin = "thinklmi/authentication/Admin";
+
+    let run = machine.expect(0, &["info"]);
+    assert!(run.stdout.contains("Admin"), "{}", run.stdout);
+    let run = machine.expect(0, &["-o", "yaml", "info"]);
+    assert!(run.stdout.contains("kbdlang: us"), "{}", run.stdout);
+
+    machine.write("password", "hunter2\n");
+    machine.firmware_writes(&format!("{}/is_enabled", admin), "1");
+    machine.expect(
+        0,
+        &[
+            "--password-file",
+            "password",
+            "--password-encoding",
+            "scancode",
+            "--kbdlang",
+            "de",
+            "set",
+            "WakeOnLAN",
+            "Disable",
+        ],
+    );
+    assert_eq!(machine.value(format!("{}/encoding", admin)), "scancode");
+    assert_eq!(machine.value(format!("{}/kbdlang", admin)), "de");
+}
+
+#[test]
+fn commit() {
+    let machine = Machine::new(LENOVO);
+
+    let run = machine.expect(0, &["set", "--commit", "bulk", "WakeOnLAN", "Disable"]);
+    assert!(run.stdout.contains("Settings saved."), "{}", run.stdout);
+    assert_eq!(machine.value("thinklmi/attributes/save_settings"), "single");
+
+    let run = machine.expect(5, &["set", "--commit", "bulk", "WakeOnLAN", "Bogus"]);
+    assert!(!run.stdout.contains("Settings saved."), "{}", run.stdout);
+    assert!(run.stderr.contains("Nothing saved"), "{}", run.stderr);
+    assert_eq!(machine.value("thinklmi/attributes/save_settings"), "single");
+
+    machine.write("profile.toml", "WakeOnLAN = \"Enable\"\n");
+    let run = machine.expect(0, &["apply", "--commit", "bulk", "profile.toml"]);
+    assert!(run.stdout.contains("Settings saved."), "{}", run.stdout);
+
+    let run = machine.expect(0, &["commit"]);
+    assert!(run.stdout.contains("Settings saved."), "{}", run.stdout);
+
+    Machine::new(DELL).expect(3, &["commit"]);
+}
+
+#[test]
+fn spm() {
+    let run = Machine::new(HP).expect(0, &["spm", "status"]);
+    assert!(
+        run.stdout.contains("State: not provisioned"),
+        "{}",
+        run.stdout
+    );
+
+    Machine::new(DELL).expect(3, &["spm", "status"]);
+}
+
+#[test]
+fn constraints() {
+    let machine = Machine::new(DELL);
+
+    let run = machine.expect(0, &["print", "AutoOnHr"]);
+    assert!(
+        run.stdout
+            .contains("has no effect while 'AutoOn' is 'Disabled'"),
+        "{}",
+        run.stdout
+    );
+    let run = machine.expect(0, &["-o", "json", "print", "LegacyOrom"]);
+    assert!(run.stdout.contains("forced-if"), "{}", run.stdout);
+
+    let run = machine.expect(5, &["set", "AutoOnHr", "6"]);
+    assert!(
+        run.stderr.contains("cannot change setting 'AutoOnHr'"),
+        "{}",
+        run.stderr
+    );
+}
+
+#[test]
+fn apply_in_dependency_order() {
+    let machine = Machine::new(DELL);
+
+    machine.write(
+        "profile.toml",
+        "LegacyOrom = \"Enabled\"\nSecureBoot = \"Disabled\"\n",
+    );
+    let run = machine.expect(0, &["apply", "profile.toml"]);
+    let changed: Vec<_> = run
+        .stdout
+        .lines()
+        .filter(|l| l.ends_with(": changed"))
+        .collect();
+    assert_eq!(
+        changed,
+        ["SecureBoot: changed", "LegacyOrom: changed"],
+        "{}",
+        run.stdout
+    );
+}
@@ -0,0 +1,416 @@
+use biosctl::{
+    testing::{Firmware, Fixture, DELL, HP, LENOVO},
+    AttributeType, AuthenticationRole, Condition, Constraint, Device, Error, Outcome, Profile,
+    ProfileValue, Secret,
+};
+use std::ffi::OsStr;
+
+fn firmware(fixture: &str) -> Firmware {
+    Firmware::new(&Fixture::parse(fixture).unwrap()).unwrap()
+}
+
+fn value(device: &Device, name: &str) -> String {
+    device
+        .attribute(OsStr::new(name))
+        .unwrap()
+        .current_value
+        .unwrap()
+}
+
+fn profile(settings: &[(&str, &str)]) -> Profile {
+    Profile {
+        settings: settings
+            .iter()
+            .map(|(n, v)| (n.to_string(), ProfileValue::String(v.to_string())))
+            .collect(),
+    }
+}
+
+#[test]
+fn enumerate() {
+    let fixture = Fixture::parse(DELL)
+        .unwrap()
+        .merge(Fixture::parse(HP).unwrap());
+    let firmware = Firmware::new(&fixture).unwrap();
+
+    let mut names: Vec<_> = firmware
+        .devices()
+        .unwrap()
+        .iter()
+        .map(|d| d.name.to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["dell-wmi-sysman", "hp-bioscfg"]);
+}
+
+#[test]
+fn attributes() {
+    for (fixture, name, count) in &[
+        (DELL, "dell-wmi-sysman", 10),
+        (LENOVO, "thinklmi", 4),
+        (HP, "hp-bioscfg", 6),
+    ] {
+        let firmware = firmware(fixture);
+        let device = firmware.device(name);
+        let attributes: Vec<_> = device.attributes().unwrap().collect();
+        assert_eq!(attributes.len(), *count, "{}", name);
+        for a in &attributes {
+            assert!(a.current_value.is_ok(), "{:?}", a.name);
+            assert!(!a.display_name.is_empty(), "{:?}", a.name);
+        }
+    }
+}
+
+#[test]
+fn unprivileged() {
+    let firmware = firmware(DELL).privileged(false);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let mut attribute = device.attribute(OsStr::new("WakeOnAc")).unwrap();
+    assert!(matches!(
+        attribute.current_value,
+        Err(Error::PermissionDenied { .. })
+    ));
+    assert_eq!(attribute.default_value.as_deref().ok(), Some("Disabled"));
+    assert!(matches!(
+        attribute.set_value(OsStr::new("Enabled")),
+        Err(Error::PermissionDenied { .. })
+    ));
+}
+
+#[test]
+fn set_value() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+    assert!(!device.modified().unwrap());
+
+    let mut attribute = device.attribute(OsStr::new("WakeOnAc")).unwrap();
+    attribute.set_value(OsStr::new("Enabled")).unwrap();
+    assert_eq!(attribute.current_value.as_deref().ok(), Some("Enabled"));
+    assert_eq!(
+        firmware
+            .read("dell-wmi-sysman/attributes/WakeOnAc/current_value")
+            .unwrap(),
+        "Enabled"
+    );
+    assert!(device.modified().unwrap());
+}
+
+#[test]
+fn set_order() {
+    let firmware = firmware(HP);
+    let device = firmware.device("hp-bioscfg");
+
+    let mut order = device.attribute(OsStr::new("UEFI Boot Order")).unwrap();
+    assert!(matches!(
+        order.tpe,
+        AttributeType::OrderedList { ref elements } if elements.len() == 4
+    ));
+    order
+        .set_order(&[
+            "USB:1",
+            "HDD:M.2:1",
+            "NETWORK IPV4:EMBEDDED:1",
+            "NETWORK IPV6:EMBEDDED:1",
+        ])
+        .unwrap();
+    assert_eq!(
+        value(&device, "UEFI Boot Order"),
+        "USB:1;HDD:M.2:1;NETWORK IPV4:EMBEDDED:1;NETWORK IPV6:EMBEDDED:1"
+    );
+    assert!(matches!(
+        order.set_order(&["USB:1"]),
+        Err(Error::InvalidValue { .. })
+    ));
+
+    let firmware = self::firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+    let mut level = device.attribute(OsStr::new("FanSpeedLvl")).unwrap();
+    assert!(matches!(
+        level.set_order(&["1"]),
+        Err(Error::NotAnOrderedList { .. })
+    ));
+}
+
+#[test]
+fn set_invalid_value() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let mut attribute = device.attribute(OsStr::new("FanSpeedLvl")).unwrap();
+    assert!(matches!(
+        attribute.set_value(OsStr::new("101")),
+        Err(Error::InvalidValue { .. })
+    ));
+    assert_eq!(value(&device, "FanSpeedLvl"), "50");
+    assert!(!device.modified().unwrap());
+
+    // Enumeration values are matched regardless of case.
+    let mut attribute = device.attribute(OsStr::new("WakeOnAc")).unwrap();
+    attribute.set_value(OsStr::new("enabled")).unwrap();
+    assert_eq!(value(&device, "WakeOnAc"), "Enabled");
+}
+
+#[test]
+fn attribute_not_found() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    assert!(matches!(
+        device.attribute(OsStr::new("Missing")),
+        Err(Error::AttributeNotFound { .. })
+    ));
+}
+
+#[test]
+fn apply() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let report = device
+        .apply(&profile(&[
+            ("WakeOnAc", "Enabled"),
+            ("WakeOnDock", "Enabled"),
+        ]))
+        .unwrap();
+    let outcomes: Vec<_> = report
+        .outcomes
+        .iter()
+        .map(|(n, o)| (n.as_str(), matches!(o, Outcome::Changed)))
+        .collect();
+    assert_eq!(outcomes, [("WakeOnAc", true), ("WakeOnDock", false)]);
+    assert!(report.reboot_pending);
+    assert_eq!(value(&device, "WakeOnAc"), "Enabled");
+}
+
+#[test]
+fn apply_invalid_profile() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let e = device
+        .apply(&profile(&[
+            ("FanSpeedLvl", "101"),
+            ("Missing", "1"),
+            ("WakeOnAc", "Enabled"),
+        ]))
+        .unwrap_err();
+    match e {
+        Error::InvalidProfile { errors } => assert!(
+            matches!(
+                errors.as_slice(),
+                [Error::InvalidValue { .. }, Error::AttributeNotFound { .. }]
+            ),
+            "{:?}",
+            errors
+        ),
+        e => panic!("unexpected error: {}", e),
+    }
+    assert_eq!(value(&device, "WakeOnAc"), "Disabled");
+}
+
+#[test]
+fn authentication() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let admin = device.admin_authentication().unwrap();
+    assert_eq!(admin.name, "Admin");
+    assert_eq!(admin.role, AuthenticationRole::BiosAdmin);
+    let system = device.authentication(OsStr::new("System")).unwrap();
+    assert_eq!(system.role, AuthenticationRole::PowerOn);
+    assert!(matches!(
+        device.authentication(OsStr::new("Missing")),
+        Err(Error::AuthenticationNotFound { .. })
+    ));
+}
+
+#[test]
+fn password() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let admin = device.admin_authentication().unwrap();
+    assert!(!admin.is_enabled);
+    assert!(matches!(
+        admin.set_password(None, &Secret::from("abc")),
+        Err(Error::PasswordLength {
+            min: 4,
+            max: 32,
+            ..
+        })
+    ));
+    admin.set_password(None, &Secret::from("hunter2")).unwrap();
+    assert!(device.admin_authentication().unwrap().is_enabled);
+}
+
+#[test]
+fn reset() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    assert_eq!(
+        device.reset_kinds().unwrap(),
+        ["builtinsafe", "lastknowngood", "factory", "custom"]
+    );
+    assert!(matches!(
+        device.reset("everything"),
+        Err(Error::InvalidResetKind { .. })
+    ));
+    device.reset("factory").unwrap();
+    assert!(device.modified().unwrap());
+}
+
+#[test]
+fn restore_default() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let mut attribute = device.attribute(OsStr::new("WakeOnAc")).unwrap();
+    assert_eq!(attribute.is_modified(), Some(false));
+    attribute.set_value(OsStr::new("Enabled")).unwrap();
+    assert_eq!(attribute.is_modified(), Some(true));
+
+    assert!(attribute.restore_default().unwrap());
+    assert!(!attribute.restore_default().unwrap());
+    assert_eq!(value(&device, "WakeOnAc"), "Disabled");
+}
+
+#[test]
+fn constraints() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let hour = device.attribute(OsStr::new("AutoOnHr")).unwrap();
+    assert_eq!(
+        hour.constraints,
+        [Constraint::SuppressedIf {
+            condition: Condition {
+                attribute: "AutoOn".to_string(),
+                value: "Disabled".to_string(),
+                equals: true,
+            }
+        }]
+    );
+}
+
+#[test]
+fn set_value_breaking_constraint() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let mut hour = device.attribute(OsStr::new("AutoOnHr")).unwrap();
+    assert!(matches!(
+        hour.set_value(OsStr::new("6")),
+        Err(Error::ConstraintViolated {
+            constraint: Constraint::SuppressedIf { .. },
+            ..
+        })
+    ));
+
+    device
+        .attribute(OsStr::new("AutoOn"))
+        .unwrap()
+        .set_value(OsStr::new("EveryDay"))
+        .unwrap();
+    hour.set_value(OsStr::new("6")).unwrap();
+    assert_eq!(value(&device, "AutoOnHr"), "6");
+}
+
+#[test]
+fn apply_breaking_constraint() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    let e = device
+        .apply(&profile(&[("AutoOnHr", "6"), ("WakeOnAc", "Enabled")]))
+        .unwrap_err();
+    match e {
+        Error::InvalidProfile { errors } => assert!(
+            matches!(errors.as_slice(), [Error::ConstraintViolated { .. }]),
+            "{:?}",
+            errors
+        ),
+        e => panic!("unexpected error: {}", e),
+    }
+    assert_eq!(value(&device, "WakeOnAc"), "Disabled");
+}
+
+#[test]
+fn apply_in_dependency_order() {
+    let firmware = firmware(DELL);
+    let device = firmware.device("dell-wmi-sysman");
+
+    // LegacyOrom is forced to Disabled while SecureBoot is Enabled, so
+    // SecureBoot is written first.
+    let report = device
+        .apply(&profile(&[
+            ("LegacyOrom", "Enabled"),
+            ("SecureBoot", "Disabled"),
+            ("WakeOnDock", "Enabled"),
+        ]))
+        .unwrap();
+    let outcomes: Vec<_> = report
+        .outcomes
+        .iter()
+        .map(|(n, o)| (n.as_str(), matches!(o, Outcome::Changed)))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("SecureBoot", true),
+            ("LegacyOrom", true),
+            ("WakeOnDock", false)
+        ]
+    );
+    assert_eq!(value(&device, "LegacyOrom"), "Enabled");
+}
+
+/// Two settings that each need the other disabled, as when HP firmware has
+/// prerequisites on both sides.
+const MUTUAL_PREREQUISITES: &str = r#"
+[hp-bioscfg.attributes]
+pending_reboot = 0
+
+[hp-bioscfg.attributes."Secure Boot"]
+type = "enumeration"
+display_name = "Secure Boot"
+current_value = "Enable"
+possible_values = ["Disable", "Enable"]
+prerequisites_size = 1
+prerequisites = 'CurrentValue("Legacy Boot Options") == "Disable"'
+
+[hp-bioscfg.attributes."Legacy Boot Options"]
+type = "enumeration"
+display_name = "Legacy Boot Options"
+current_value = "Disable"
+possible_values = ["Disable", "Enable"]
+prerequisites_size = 1
+prerequisites = 'CurrentValue("Secure Boot") == "Disable"'
+"#;
+
+#[test]
+fn apply_with_mutual_prerequisites() {
+    let firmware = firmware(MUTUAL_PREREQUISITES);
+    let device = firmware.device("hp-bioscfg");
+
+    let report = device
+        .apply(&profile(&[
+            ("Legacy Boot Options", "Enable"),
+            ("Secure Boot", "Disable"),
+        ]))
+        .unwrap();
+    assert_eq!(report.failed().count(), 0);
+    assert_eq!(value(&device, "Legacy Boot Options"), "Enable");
+    assert_eq!(value(&device, "Secure Boot"), "Disable");
+
+    // And back, which needs the opposite order.
+    let report = device
+        .apply(&profile(&[
+            ("Secure Boot", "Enable"),
+            ("Legacy Boot Options", "Disable"),
+        ]))
+        .unwrap();
+    assert_eq!(report.failed().count(), 0);
+    assert_eq!(value(&device, "Secure Boot"), "Enable");
+}